@@ -0,0 +1,378 @@
+use anyhow::Result;
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use qrcode::{render::unicode, QrCode};
+use std::sync::Mutex;
+use std::time::Duration;
+use vl::catcher::auth::{self, QrStatus};
+use vl::catcher::collection::{self, Source};
+use vl::catcher::link::{self, quality_name};
+use vl::catcher::pgc::{self, SeasonRsp};
+use vl::catcher::view::{self, ViewRsp};
+use vl::downloader::{Downloader, Format};
+use vl::event::Event;
+use vl::id::BangumiId;
+use vl::item::{Item, ItemOptions};
+use vl::loader::{judge, StreamProtocol};
+
+use crate::config::{init_file, json_schema, mask_item, set_file_item, ITEM_DOCS};
+use crate::output::{emit, json_output, print_text};
+use crate::parse::{
+    parse_input, parse_room, save_cookies, CONFIG, CONFIG_BUILDER, CONFIG_FILE, CONTEXT,
+};
+use crate::{downloader, watch};
+
+fn format_duration(seconds: usize) -> String {
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn print_view(view: &ViewRsp) {
+    print_text!("Title:    {}", view.title);
+    print_text!("Id:       {} / av{}", view.bvid, view.aid);
+    print_text!("Uploader: {}", view.owner.name);
+    print_text!("Zone:     {}", view.tname);
+    print_text!("Duration: {}", format_duration(view.duration));
+    print_text!("Pages:    {}", view.videos);
+    for (index, page) in view.pages.iter().enumerate() {
+        print_text!(
+            "  P{} cid={} {} {}",
+            index + 1,
+            page.cid,
+            format_duration(page.duration),
+            page.part
+        );
+    }
+}
+
+fn print_season(season: &SeasonRsp) {
+    print_text!("Title:    {}", season.title);
+    print_text!("Id:       ss{}", season.season_id);
+    print_text!("Producer: {}", season.up_info.uname);
+    print_text!("Episodes: {}", season.episodes.len());
+    let print_episodes = |episodes: &[pgc::Episode]| {
+        for (index, episode) in episodes.iter().enumerate() {
+            print_text!(
+                "  {} ep{} {} {}",
+                index + 1,
+                episode.id,
+                format_duration(episode.duration / 1000),
+                episode.name()
+            );
+        }
+    };
+    print_episodes(&season.episodes);
+    for section in season.section.iter().filter(|s| !s.episodes.is_empty()) {
+        print_text!("{}:", section.title);
+        print_episodes(&section.episodes);
+    }
+}
+
+/// Print one stream with the size estimated by a HEAD request.
+async fn print_stream(kind: &str, audio: &link::Audio) {
+    let size = match judge(&CONTEXT.client, &audio.base_url).await {
+        Ok((content_length, _)) => format!("{:.2}MB", content_length as f64 / 1024.0 / 1024.0),
+        Err(_) => "unknown size".to_string(),
+    };
+    print_text!(
+        "  {kind:<5} [{}] {:<6} {:<10} {:<10} {}kbps {size}",
+        audio.id,
+        quality_name(audio.id),
+        audio.codecs,
+        audio.mime_type,
+        audio.bandwidth / 1000,
+    );
+}
+
+async fn print_streams(dash: &link::Dash) {
+    print_text!("Audio streams:");
+    for audio in &dash.audio {
+        print_stream("audio", audio).await;
+    }
+    if let Some(audio) = dash.flac.as_ref().and_then(|flac| flac.audio.as_ref()) {
+        print_stream("flac", audio).await;
+    }
+    if let Some(audios) = dash.dolby.as_ref().and_then(|dolby| dolby.audio.as_ref()) {
+        for audio in audios {
+            print_stream("dolby", audio).await;
+        }
+    }
+}
+
+/// Print information and available audio streams of a video or a bangumi.
+pub async fn info(input: &str) -> Result<()> {
+    let item = parse_input(input).await?;
+    if let Ok(id) = item.id.parse::<BangumiId>() {
+        let season = pgc::season(&CONTEXT, id).await?;
+        print_season(&season);
+
+        let (_, episode) = season.select(id, None)?[0];
+        let link = pgc::playurl(&CONTEXT, episode.id, episode.cid, 16 | 256).await?;
+        print_streams(&link.dash).await;
+        return Ok(());
+    }
+    let view = view::api(&CONTEXT, &item.id).await?;
+    print_view(&view);
+
+    let link = link::api(&CONTEXT, &view.bvid, view.pages[0].cid, 16 | 256, None).await?;
+    print_streams(&link.dash).await;
+
+    Ok(())
+}
+
+/// Record a live room, Ctrl-C stops recording and what has been recorded is still saved.
+pub async fn record(
+    room: &str,
+    duration: Option<Duration>,
+    protocol: StreamProtocol,
+    format: Option<Format>,
+    filename: Option<&str>,
+) -> Result<()> {
+    let room_id = parse_room(room).await?;
+    watch::listen_shutdown();
+    let options = ItemOptions {
+        name: filename.map(str::to_string),
+        format,
+        ..Default::default()
+    };
+    downloader(None)
+        .record(room_id, duration, protocol, &options)
+        .await?;
+    Ok(())
+}
+
+/// Print the selected stream, sent as the event of downloading it too.
+fn print_selected(
+    downloader: &Downloader,
+    index: usize,
+    (format, stream): (Format, &link::Audio),
+    filename: &str,
+) {
+    emit(Event::StreamSelected {
+        index,
+        quality: stream.id,
+        codecs: stream.codecs.clone(),
+        format: format.extension().to_string(),
+        bandwidth: stream.bandwidth,
+    });
+    let output = downloader
+        .output_dir()
+        .join(filename)
+        .with_extension(format.extension());
+    print_text!(
+        "Selected: [{}] {} -> {}",
+        stream.id,
+        quality_name(stream.id),
+        output.display()
+    );
+}
+
+/// Print what the download command would do for an id, nothing is written.
+///
+/// The events of metadata and selected streams are sent like downloading.
+pub async fn simulate(downloader: &Downloader, index: usize, item: &Item) -> Result<()> {
+    let (id, options) = (&item.id, &item.options);
+    if let Ok(bangumi) = id.parse::<BangumiId>() {
+        let season = pgc::season(&CONTEXT, bangumi).await?;
+        print_text!("[{index}] {id}");
+        print_season(&season);
+
+        let episodes = season.select(bangumi, options.pages.as_ref())?;
+        emit(Event::MetadataFetched {
+            index,
+            bvid: id.to_string(),
+            title: season.title.clone(),
+            owner: season.up_info.uname.clone(),
+            duration: episodes.iter().map(|(_, e)| e.duration / 1000).sum(),
+            pages: episodes.len(),
+        });
+        for &(track, episode) in &episodes {
+            let link = pgc::playurl(&CONTEXT, episode.id, episode.cid, 16 | 256).await?;
+            print_text!("ep{} {}:", episode.id, episode.name());
+            print_streams(&link.dash).await;
+
+            let selected = downloader.select_stream_with(&link.dash, options)?;
+            let filename =
+                downloader.episode_filename(&season, episode, options, track, episodes.len());
+            print_selected(downloader, index, selected, &filename);
+        }
+        print_text!();
+        return Ok(());
+    }
+    let view = view::api(&CONTEXT, id).await?;
+    print_text!("[{index}] {id}");
+    print_view(&view);
+    emit(Event::MetadataFetched {
+        index,
+        bvid: view.bvid.clone(),
+        title: view.title.clone(),
+        owner: view.owner.name.clone(),
+        duration: view.duration,
+        pages: view.videos,
+    });
+
+    // Same as downloading, pages selecting nothing are an error.
+    let pages = options.select_pages(&view.bvid, view.pages.len())?;
+    for &page in &pages {
+        let cid = view.pages[page - 1].cid;
+        let link = link::api(&CONTEXT, &view.bvid, cid, 16 | 256, None).await?;
+        if pages.len() > 1 {
+            print_text!("P{page}:");
+        }
+        print_streams(&link.dash).await;
+
+        let selected = downloader.select_stream_with(&link.dash, options)?;
+        let filename = downloader.page_filename(&view.title, options, page, pages.len());
+        print_selected(downloader, index, selected, &filename);
+    }
+    print_text!();
+
+    Ok(())
+}
+
+/// Print all videos of a collection.
+pub async fn list(source: &Source) -> Result<()> {
+    let items = collection::api(&CONTEXT, source, None).await?;
+    for item in &items {
+        match json_output() {
+            // One video per line like the events, the list is the result of the command.
+            true => println!(
+                "{}",
+                serde_json::json!({ "bvid": item.bvid, "title": item.title })
+            ),
+            false => println!("{}\t{}", item.bvid, item.title),
+        }
+    }
+    info!("Total: {}", items.len());
+    Ok(())
+}
+
+fn save_session(session: &str, csrf: &str, refresh_token: &str) -> Result<()> {
+    let (path, profile) = (&*CONFIG_FILE, &CONFIG.profile());
+    set_file_item(path, profile, "session", Some(session.into()))?;
+    set_file_item(path, profile, "bili_jct", Some(csrf.into()))?;
+    set_file_item(path, profile, "refresh_token", Some(refresh_token.into()))?;
+    Ok(())
+}
+
+/// Refresh token of the session, replaced after every refresh.
+static REFRESH_TOKEN: Lazy<Mutex<String>> = Lazy::new(|| Mutex::new(CONFIG.refresh_token()));
+
+/// Refresh the cookies when bilibili asks to, the new session is saved to config file.
+pub async fn refresh_session() {
+    let refresh_token = REFRESH_TOKEN.lock().unwrap().clone();
+    if refresh_token.is_empty() {
+        return;
+    }
+    match auth::refresh_cookies(&CONTEXT, &refresh_token).await {
+        Ok(None) => {}
+        Ok(Some(refreshed)) => {
+            *REFRESH_TOKEN.lock().unwrap() = refreshed.refresh_token.clone();
+            match save_session(
+                &refreshed.session,
+                &refreshed.csrf,
+                &refreshed.refresh_token,
+            ) {
+                Ok(()) => info!("Session refreshed and saved to {}", *CONFIG_FILE),
+                Err(e) => warn!("Session refreshed but failed to save it: {e}"),
+            }
+            save_cookies();
+        }
+        Err(e) => {
+            warn!("Failed to refresh session, login again or set bili_jct by cookies file: {e}")
+        }
+    }
+}
+
+/// Login by QR code and save the session to config file.
+pub async fn login() -> Result<()> {
+    let qrcode = auth::qrcode_generate(&CONTEXT).await?;
+
+    let code = QrCode::new(qrcode.url.as_bytes())?;
+    let image = code
+        .render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build();
+    print_text!("{image}");
+    print_text!(
+        "Scan the QR code with bilibili app, or open: {}",
+        qrcode.url
+    );
+
+    let mut scanned = false;
+    loop {
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        match auth::qrcode_poll(&CONTEXT, &qrcode.qrcode_key).await? {
+            QrStatus::Waiting => {}
+            QrStatus::Scanned => {
+                if !scanned {
+                    info!("QR code scanned, please confirm on your phone");
+                    scanned = true;
+                }
+            }
+            QrStatus::Expired => return Err(anyhow::Error::msg("QR code expired, try again.")),
+            QrStatus::Confirmed {
+                session,
+                csrf,
+                refresh_token,
+            } => {
+                save_session(&session, &csrf, &refresh_token)?;
+                info!("Login succeed, session saved to {}", *CONFIG_FILE);
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Remove the saved session from config file.
+pub fn logout() -> Result<()> {
+    let (path, profile) = (&*CONFIG_FILE, &CONFIG.profile());
+    set_file_item(path, profile, "session", None)?;
+    set_file_item(path, profile, "bili_jct", None)?;
+    set_file_item(path, profile, "refresh_token", None)?;
+    info!("Session removed from {}", *CONFIG_FILE);
+    Ok(())
+}
+
+pub fn config_show(sources: bool, show_secrets: bool) {
+    let items = serde_json::to_value(&*CONFIG).unwrap_or_default();
+    for (name, _) in ITEM_DOCS {
+        let value = match items[name].as_str() {
+            Some(value) if !show_secrets => mask_item(name, value).into(),
+            _ => items[name].clone(),
+        };
+        match sources {
+            true => println!(
+                "\x1b[32m[*]\x1b[0m {name} => {value} \x1b[90m({})\x1b[0m",
+                CONFIG_BUILDER.source(name)
+            ),
+            false => println!("\x1b[32m[*]\x1b[0m {name} => {value}"),
+        }
+    }
+}
+
+pub fn config_save(path: &str) -> Result<()> {
+    CONFIG_BUILDER.to_file(path)?;
+    // Items of the profile are merged already, and the file has no profiles.
+    set_file_item(path, "", "profile", None)?;
+    info!("Effective config written to {path}");
+    Ok(())
+}
+
+pub fn config_schema() {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&json_schema()).unwrap_or_default()
+    );
+}
+
+pub fn config_init(force: bool) -> Result<()> {
+    let path = &*CONFIG_FILE;
+    if !force && std::path::Path::new(path).exists() {
+        warn!("{path} already exists, use --force to overwrite it");
+        return Ok(());
+    }
+    init_file(path)?;
+    info!("Config file written to {path}");
+    Ok(())
+}
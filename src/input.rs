@@ -0,0 +1,179 @@
+use vl::item::{Item, ItemOptions};
+
+/// Split a line into words by whitespace, double quoted words can have spaces, `\"` and `\\`.
+///
+/// A word starting with `#` starts a comment, `#` inside a word is kept, e.g. url fragments.
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.peek() {
+            None | Some('#') => break,
+            _ => {}
+        }
+        let mut word = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => quoted = !quoted,
+                '\\' if quoted => match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err("unfinished escape".to_string()),
+                },
+                c if c.is_whitespace() && !quoted => break,
+                c => word.push(c),
+            }
+        }
+        if quoted {
+            return Err("unclosed quote".to_string());
+        }
+        words.push(word);
+    }
+    Ok(words)
+}
+
+fn set_option(options: &mut ItemOptions, key: &str, value: &str) -> Result<(), String> {
+    let parse = |e: vl::Error| e.to_string();
+    match key {
+        "name" => options.name = Some(value.to_string()),
+        "format" => options.format = Some(value.parse().map_err(parse)?),
+        "quality" => options.quality = Some(value.to_string()),
+        "pages" | "p" => options.pages = Some(value.parse().map_err(parse)?),
+        "range" => options.range = Some(value.parse().map_err(parse)?),
+        _ => match key.strip_prefix("tag.").filter(|tag| !tag.is_empty()) {
+            Some(tag) => {
+                options.tags.insert(tag.to_string(), value.to_string());
+            }
+            None => return Err(format!("unknown option \"{key}\"")),
+        },
+    }
+    Ok(())
+}
+
+/// Parse a line of `<input> [key=value]...`, `None` for blank lines and comments.
+///
+/// Keys are name, format, quality, pages, range and tag.<name>.
+pub fn parse_line(line: &str) -> Option<Result<Item, String>> {
+    let words = match split_words(line) {
+        Ok(words) => words,
+        Err(e) => return Some(Err(e)),
+    };
+    let (input, pairs) = words.split_first()?;
+    let mut options = ItemOptions::default();
+    for pair in pairs {
+        let result = match pair.split_once('=') {
+            Some((key, value)) => set_option(&mut options, key, value),
+            None => Err(format!("\"{pair}\" should be key=value")),
+        };
+        if let Err(e) = result {
+            return Some(Err(e));
+        }
+    }
+    Some(Ok(Item::new(input.as_str(), options)))
+}
+
+/// Items of a json array, an item is an input string or an object with `id` and options.
+fn parse_json(content: &str) -> Result<Vec<Item>, String> {
+    let values: Vec<serde_json::Value> =
+        serde_json::from_str(content).map_err(|e| e.to_string())?;
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let item = |e: String| format!("item {}: {e}", index + 1);
+            match value {
+                serde_json::Value::String(input) => Ok(Item::from(input)),
+                serde_json::Value::Object(mut object) => {
+                    let id = match object.remove("id") {
+                        Some(serde_json::Value::String(id)) => id,
+                        _ => return Err(item("\"id\" should be a string".to_string())),
+                    };
+                    let options =
+                        serde_json::from_value(object.into()).map_err(|e| item(e.to_string()))?;
+                    Ok(Item::new(id, options))
+                }
+                _ => Err(item("expect a string or an object".to_string())),
+            }
+        })
+        .collect()
+}
+
+/// Items of the input file, in json if it starts with `[`, or one item per line.
+///
+/// Errors are the line numbers and messages of illegal lines, other lines are kept.
+pub fn parse_file(content: &str) -> (Vec<Item>, Vec<String>) {
+    if content.trim_start().starts_with('[') {
+        return match parse_json(content) {
+            Ok(items) => (items, vec![]),
+            Err(e) => (vec![], vec![e]),
+        };
+    }
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    // `lines` drops the `\r` of `\r\n` too.
+    for (number, line) in content.lines().enumerate() {
+        match parse_line(line) {
+            None => {}
+            Some(Ok(item)) => items.push(item),
+            Some(Err(e)) => errors.push(format!("line {}: {e}", number + 1)),
+        }
+    }
+    (items, errors)
+}
+
+#[test]
+fn parse_file_test() {
+    let content = "# songs\r\n\
+        BV12g411r7mB\r\n\
+        \r\n\
+        https://www.bilibili.com/video/BV1fB4y1h76Z#reply   # fragment is kept\r\n\
+        av170001 name=\"Live at \\\"Hall\\\"\" format=flac quality=192K pages=1-2 range=0:30-2:15 tag.album=\"Best of\"\n\
+        BV1xx411c7mD foo=bar\n\
+        BV1xx411c7mD pages=0\n";
+    let (items, errors) = parse_file(content);
+    assert_eq!(
+        items
+            .iter()
+            .map(|item| item.id.as_str())
+            .collect::<Vec<_>>(),
+        vec![
+            "BV12g411r7mB",
+            "https://www.bilibili.com/video/BV1fB4y1h76Z#reply",
+            "av170001"
+        ]
+    );
+    assert_eq!(items[0].options, ItemOptions::default());
+    let options = &items[2].options;
+    assert_eq!(options.name.as_deref(), Some("Live at \"Hall\""));
+    assert_eq!(options.format, Some(vl::downloader::Format::Flac));
+    assert_eq!(options.quality.as_deref(), Some("192K"));
+    assert_eq!(options.pages.as_ref().unwrap().select(3), vec![1, 2]);
+    assert_eq!(options.range.unwrap().start, 30.0);
+    assert_eq!(
+        options.tags,
+        std::collections::BTreeMap::from([("album".to_string(), "Best of".to_string())])
+    );
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].starts_with("line 6: unknown option \"foo\""));
+    assert!(errors[1].starts_with("line 7: "));
+}
+
+#[test]
+fn parse_json_test() {
+    let content = r#"[
+        "BV12g411r7mB",
+        {"id": "av170001", "name": "x", "pages": "all", "tags": {"genre": "Vocaloid"}}
+    ]"#;
+    let (items, errors) = parse_file(content);
+    assert!(errors.is_empty());
+    assert_eq!(items[0], Item::from("BV12g411r7mB"));
+    assert_eq!(items[1].options.tags["genre"], "Vocaloid");
+    assert_eq!(
+        items[1].options.pages.as_ref().unwrap().select(2),
+        vec![1, 2]
+    );
+
+    let (_, errors) = parse_file(r#"[{"id": "av1", "qualty": "64K"}]"#);
+    assert!(errors[0].starts_with("item 1: unknown field `qualty`"));
+}
@@ -1,196 +1,139 @@
-use anyhow::Result;
-use log::error;
-use log::info;
-use log::warn;
+use log::{info, warn};
 use std::path::PathBuf;
-use std::time::Duration;
-use std::time::Instant;
-use tokio::join;
 use tokio::runtime;
-use tokio::sync::mpsc;
 
 use parse::CONFIG;
-use tokio::sync::mpsc::Sender;
-use vl::catcher::link;
-use vl::catcher::view;
-use vl::loader::load;
-use vl::transfer;
+use vl::archive::Archive;
+use vl::downloader::{Downloader, ItemResult};
+use vl::event::ErrorKind;
+use vl::item::Item;
 
-use crate::parse::SESSION;
-use crate::util::safe_filename;
+use crate::output::{emit, error_kind, exit_code, hint, report};
+use crate::parse::{Command, ConfigAction, ARGS, CONTEXT};
 
+mod command;
 mod config;
+mod input;
+mod output;
 mod parse;
 mod util;
-
-#[derive(PartialEq)]
-enum Audio {
-    Flac,
-    M4a,
+mod watch;
+
+/// Build the downloader from config, events are emitted by the output format.
+pub fn downloader(archive: Option<Archive>) -> Downloader {
+    let mut builder = Downloader::builder()
+        .context(CONTEXT.clone())
+        .output_dir(CONFIG.path())
+        .flac_allowed(CONFIG.flac_allowed())
+        .pic_allowed(CONFIG.pic_allowed())
+        .filename(CONFIG.filename())
+        .on_event(emit)
+        .cancel(watch::SHUTDOWN.clone());
+    if let Some(archive) = archive {
+        builder = builder.archive(archive);
+    }
+    builder.build()
 }
 
-async fn run_one_by_one(index: usize, id: &str, tx: Sender<Context>) -> Result<()> {
-    // Get audio information.
-    info!("[{index}] Get information about {}", id);
-    let view = view::api(id).await?;
-
-    // Get audio link.
-    let link = link::api(
-        &view.bvid,
-        view.pages[0].cid,
-        16 | 256,
-        Some(SESSION.clone()),
-    )
-    .await?;
-
-    // Download audio.
-    info!("[{index}] Downloading {}", id);
-    // If ids.len() > 1, you should not to set filename.
-    let filename = match !CONFIG.filename().is_empty() {
-        true => CONFIG.filename(),
-        false => view.title.to_string(),
-    };
-    let filename = safe_filename(&filename);
-
-    // Set default audio type.
-    let mut audio_type = Audio::M4a;
-    let mut load_url = link.dash.audio[0].base_url.to_string();
-
-    if CONFIG.flac_allowed() {
-        if let Some(flac) = link.dash.flac {
-            load_url = flac.audio.unwrap().base_url;
-            audio_type = Audio::Flac;
-        }
+fn open_archive(name: &str) -> vl::Result<Option<Archive>> {
+    if name.is_empty() {
+        return Ok(None);
     }
+    let archive = Archive::open(PathBuf::from(CONFIG.path()).join(name))?;
+    info!(
+        "Using download archive {:?} with {} records",
+        archive.path(),
+        archive.len()
+    );
+    Ok(Some(archive))
+}
 
-    load(&load_url, &filename, &CONFIG.path(), "m4s").await?;
-
-    if CONFIG.pic_allowed() {
-        load(&view.pic, &filename, &CONFIG.path(), "jpg").await?;
+/// Returns the kind of the last error if any item failed.
+pub async fn run() -> Option<ErrorKind> {
+    if ARGS.download_inputs().is_some_and(|inputs| inputs.dry_run) {
+        let downloader = downloader(None);
+        let mut last_error = None;
+        for (index, item) in parse::items().await.iter().enumerate() {
+            if let Err(e) = command::simulate(&downloader, index + 1, item).await {
+                report(
+                    &format!("[{}] Error occurs when viewing audio: ", index + 1),
+                    &e,
+                );
+                last_error = Some(error_kind(&e));
+            }
+        }
+        return last_error;
     }
 
-    // Preparing for transform audio.
-    let context = Context {
-        index,
-        audio: audio_type,
-        filename: filename.to_string(),
-        owner: view.owner.name,
+    let archive = match open_archive(&CONFIG.archive()) {
+        Ok(archive) => archive,
+        Err(e) => {
+            let e = e.into();
+            report("Failed to open download archive: ", &e);
+            return Some(error_kind(&e));
+        }
     };
 
-    tokio::spawn(async move {
-        let _ = tx.send(context).await;
-    });
-
-    Ok(())
+    run_items(&downloader(archive), &parse::items().await).await
 }
 
-struct Context {
-    index: usize,
-    audio: Audio,
-    filename: String,
-    owner: String,
+/// Indexes of items blocked by risk control.
+fn blocked_indexes(results: &[ItemResult]) -> Vec<usize> {
+    results
+        .iter()
+        .filter(|item| matches!(&item.result, Err(e) if e.kind() == ErrorKind::RiskControl))
+        .map(|item| item.index)
+        .collect()
 }
 
-pub async fn run() {
-    let total_cost = Instant::now();
-
-    let ids = CONFIG.id();
-
-    let (tx, mut rx) = mpsc::channel::<Context>(ids.len());
-
-    let transform_handler = tokio::spawn(async move {
-        while let Some(context) = rx.recv().await {
-            info!(
-                "[{}] Starting transform '{}'",
-                context.index, context.filename
-            );
-
-            let extension = match context.audio {
-                Audio::M4a => "m4a",
-                Audio::Flac => "flac",
-            };
-
-            let path = PathBuf::from(CONFIG.path()).join(&context.filename);
-
-            let source = path.with_extension("m4s");
-            let output = path.with_extension(extension);
-
-            if !source.exists() {
-                error!("[{}] Source file not exists.", context.index);
-                let _ = std::fs::remove_file(source.clone());
-                if CONFIG.pic_allowed() {
-                    let _ = std::fs::remove_file(source.with_extension("jpg"));
-                }
-                continue;
-            }
-
-            if output.exists() {
-                error!("[{}] Output file already exists.", context.index);
-                let _ = std::fs::remove_file(source.clone());
-                if CONFIG.pic_allowed() {
-                    let _ = std::fs::remove_file(source.with_extension("jpg"));
-                }
+/// Download the given items, returns the kind of the last error if any item failed.
+///
+/// Items blocked by risk control are retried with the accounts of fallback profiles in turn.
+pub async fn run_items(downloader: &Downloader, items: &[Item]) -> Option<ErrorKind> {
+    let mut results = downloader.run_items(items).await;
+    for profile in parse::split_list(&CONFIG.fallback_profiles()) {
+        let blocked = blocked_indexes(&results);
+        if blocked.is_empty() {
+            break;
+        }
+        let ctx = match parse::profile_context(&profile) {
+            Ok(ctx) => ctx,
+            Err(e) => {
+                warn!("Fallback profile {profile} is skipped: {e}");
                 continue;
             }
-
-            let pic = source.with_extension("jpg");
-            let pic = match CONFIG.pic_allowed() {
-                true => pic.to_str(),
-                false => None,
-            };
-
-            if let Err(e) = transfer::run(
-                source.to_str().unwrap(),
-                output.to_str().unwrap(),
-                pic,
-                extension,
-                &context.filename,
-                &context.owner,
-            )
-            .await
-            {
-                error!("[{}] {e}", context.index);
-            }
-
-            let _ = std::fs::remove_file(source.clone());
-            if CONFIG.pic_allowed() {
-                let _ = std::fs::remove_file(source.with_extension("jpg"));
-            }
-            info!(
-                "[{}] Finish transforming '{}'",
-                context.index, context.filename
-            );
+        };
+        warn!(
+            "{} items are blocked by risk control, retrying with profile {profile}",
+            blocked.len()
+        );
+        let retry = blocked
+            .iter()
+            .map(|index| items[index - 1].clone())
+            .collect::<Vec<_>>();
+        results.retain(|item| !(blocked.contains(&item.index) && item.result.is_err()));
+        for mut retried in downloader.with_context(ctx).run_items(&retry).await {
+            retried.index = blocked[retried.index - 1];
+            results.push(retried);
         }
-    });
-
-    for (index, id) in ids.iter().enumerate() {
-        let index = index + 1;
-
-        info!("[{index}] id = {id} starting to work");
+        results.sort_by_key(|item| item.index);
+    }
 
-        if let Err(e) = run_one_by_one(index, id, tx.clone()).await {
-            error!(
-                "[{index}] Error occurs when viewing or downloading audio: {}",
-                e.source().unwrap()
-            );
+    let mut last_error = None;
+    for item in &results {
+        if let Err(e) = &item.result {
+            if let Some(hint) = hint(e.kind()) {
+                warn!("[{}] {hint}", item.index);
+            }
+            last_error = Some(e.kind());
         }
-
-        info!("[{index}] id = {id} finish download");
     }
-    drop(tx);
-
-    let _ = join!(transform_handler);
-
-    summary(total_cost.elapsed());
-}
-
-fn summary(cost: Duration) {
-    info!("Total costs: {:?}", cost);
+    last_error
 }
 
 fn pre_work() {
     // TODO: 添加session有效性验证
-    match CONFIG.session().is_empty() {
+    match CONTEXT.client.cookies().get("SESSDATA").is_none() {
         true => warn!("You are not set account session, so can't download flac/dolby"),
         false => info!("You have already set account session, can download flac/dolby if supports"),
     };
@@ -198,10 +141,70 @@ fn pre_work() {
 
 fn main() {
     simple_logger::init_with_level(log::Level::Info).unwrap();
-    pre_work();
     let runtime = runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .unwrap();
-    runtime.block_on(run());
+
+    // A dry run writes nothing, neither the refreshed session nor the cookies.
+    let dry_run = ARGS.download_inputs().is_some_and(|inputs| inputs.dry_run);
+    let mut failed = None;
+    let result = match &ARGS.command {
+        None | Some(Command::Download(_)) => {
+            // Config and inputs are checked before any request, the refresh needs the session of config.
+            pre_work();
+            if !dry_run {
+                runtime.block_on(command::refresh_session());
+            }
+            failed = runtime.block_on(run());
+            Ok(())
+        }
+        Some(Command::Watch { .. }) => {
+            pre_work();
+            runtime.block_on(watch::watch())
+        }
+        Some(Command::Record {
+            room,
+            duration,
+            protocol,
+            format,
+            filename,
+        }) => runtime.block_on(command::record(
+            room,
+            *duration,
+            *protocol,
+            *format,
+            filename.as_deref(),
+        )),
+        Some(Command::Info { id }) => runtime.block_on(command::info(id)),
+        Some(Command::List { source }) => runtime.block_on(command::list(source)),
+        Some(Command::Login) => runtime.block_on(command::login()),
+        Some(Command::Logout) => command::logout(),
+        Some(Command::Config { action }) => match action {
+            ConfigAction::Show {
+                sources,
+                show_secrets,
+            } => {
+                command::config_show(*sources, *show_secrets);
+                Ok(())
+            }
+            ConfigAction::Save { file } => command::config_save(file),
+            ConfigAction::Init { force } => command::config_init(*force),
+            ConfigAction::Schema => {
+                command::config_schema();
+                Ok(())
+            }
+        },
+    };
+
+    if !dry_run {
+        parse::save_cookies();
+    }
+    if let Err(e) = result {
+        report("", &e);
+        std::process::exit(exit_code(error_kind(&e)));
+    }
+    if let Some(kind) = failed {
+        std::process::exit(exit_code(kind));
+    }
 }
@@ -0,0 +1,88 @@
+use clap::ValueEnum;
+use log::error;
+use once_cell::sync::Lazy;
+use vl::event::{ErrorKind, Event};
+
+use crate::parse::ARGS;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Logs for human
+    Human,
+    /// One json event per line on stdout
+    Json,
+}
+
+static JSON_OUTPUT: Lazy<bool> = Lazy::new(|| ARGS.global.output_format() == OutputFormat::Json);
+
+pub fn json_output() -> bool {
+    *JSON_OUTPUT
+}
+
+/// `println!` of text for human, which goes to stderr with json output so that stdout
+/// has json lines only.
+macro_rules! print_text {
+    ($($arg:tt)*) => {
+        match $crate::output::json_output() {
+            true => eprintln!($($arg)*),
+            false => println!($($arg)*),
+        }
+    };
+}
+pub(crate) use print_text;
+
+/// Print the event to stdout as a json line when json output is enabled.
+pub fn emit(event: Event) {
+    if *JSON_OUTPUT {
+        println!("{}", serde_json::to_string(&event).unwrap());
+    }
+}
+
+/// Kind of the first vl error in the chain.
+pub fn error_kind(error: &anyhow::Error) -> ErrorKind {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<vl::Error>())
+        .map_or(ErrorKind::Other, vl::Error::kind)
+}
+
+/// Exit code of the process for an error kind, 2 is taken by clap for usage errors.
+pub fn exit_code(kind: ErrorKind) -> i32 {
+    match kind {
+        ErrorKind::Other => 1,
+        ErrorKind::Network => 3,
+        ErrorKind::Api => 4,
+        ErrorKind::RiskControl => 5,
+        ErrorKind::NeedLogin => 6,
+        ErrorKind::NotFound => 7,
+        ErrorKind::NoAudioStream => 8,
+        ErrorKind::RangeUnsupported => 9,
+        ErrorKind::Transcode => 10,
+        ErrorKind::Io => 11,
+        ErrorKind::Parse => 12,
+        ErrorKind::InvalidInput => 13,
+        ErrorKind::Forbidden => 14,
+    }
+}
+
+/// What the user could do about an error.
+pub fn hint(kind: ErrorKind) -> Option<&'static str> {
+    match kind {
+        ErrorKind::Network => Some("check your network connection"),
+        ErrorKind::RiskControl => Some("requests are too frequent, wait a while or login first"),
+        ErrorKind::NeedLogin => Some("run `voiceload login` or set a session"),
+        ErrorKind::NotFound => Some("check the id, the video may be deleted or hidden"),
+        ErrorKind::Forbidden => Some("the video is region limited or needs more privilege"),
+        ErrorKind::NoAudioStream => Some("the video has no audio to download"),
+        ErrorKind::Transcode => Some("make sure ffmpeg is in the same directory"),
+        _ => None,
+    }
+}
+
+/// Log an error with its hint.
+pub fn report(prefix: &str, error: &anyhow::Error) {
+    match hint(error_kind(error)) {
+        Some(hint) => error!("{prefix}{error} ({hint})"),
+        None => error!("{prefix}{error}"),
+    }
+}
@@ -1,180 +1,624 @@
-use clap::Parser;
-use log::{error, info};
-use once_cell::sync::Lazy;
-use reqwest::{
-    blocking::{self, Client},
-    header::{self, HeaderMap, HeaderValue},
-    redirect::Policy,
-    IntoUrl, Url,
-};
+use clap::{Parser, Subcommand};
+use log::{error, info, warn};
+use once_cell::sync::{Lazy, OnceCell};
 
 use crate::{
-    config::{ConfigBuilder, ConfigItems},
-    util::{is_id, is_link, read_file_string},
+    config::{config_dirs, find_config, load_env_file, ConfigBuilder, ConfigItems},
+    input,
+    output::{exit_code, OutputFormat},
+    util::{extract_inputs, read_input_string},
+    watch::WatchSources,
 };
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use vl::catcher::collection::Source;
+use vl::cookies::{CookieFormat, CookieJar};
+use vl::downloader::Format;
+use vl::event::ErrorKind;
+use vl::item::{parse_time, Item, ItemOptions, Pages};
+use vl::loader::StreamProtocol;
+use vl::resolve::{resolve, resolve_all, Target};
+use vl::{Client as VlClient, ClientConfig, Context, ProxyConfig};
 
 #[derive(Parser, Debug)]
 #[command(name = "bili-voiceload", author, version, about, long_about = None)]
 pub struct Args {
-    /// aid/bvid/link to download, can be multiple
-    #[arg(short, long)]
-    inputs: Option<Vec<String>>,
+    /// Inputs of the default download command
+    #[command(flatten)]
+    pub inputs: InputArgs,
 
-    /// parsing a file content line by line to get input, split by '\n'
-    #[arg(short, long)]
-    file_input: Option<String>,
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
 
+/// Options shared by all commands.
+#[derive(clap::Args, Debug)]
+pub struct GlobalArgs {
     /// Allow downloading flac [default: false]
-    #[arg(short = 'F', long)]
+    #[arg(short = 'F', long, global = true)]
     flac_allowed: Option<bool>,
 
     /// Allow downloading dolby [default: false]
-    #[arg(short = 'D', long)]
+    #[arg(short = 'D', long, global = true)]
     dolby_allowed: Option<bool>,
 
     /// Allow adding picture to audio [default: false]
-    #[arg(short = 'P', long)]
+    #[arg(short = 'P', long, global = true)]
     picture_allowed: Option<bool>,
 
     /// Path to save audio files [default: ./]
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     path: Option<String>,
 
+    /// (Optional) Sessiondata for login aiming to dolby or flac [default: None]
+    #[arg(short, long, global = true)]
+    session: Option<String>,
+
+    /// (Optional) Profile in "profiles" of config file to use [default: None]
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// (Optional) Cookies file exported from browser, Netscape cookies.txt or JSON, updated cookies are written back [default: None]
+    #[arg(long, global = true)]
+    cookies: Option<String>,
+
+    /// (Optional) Archive file to record and skip downloaded audio, relative to path [default: None]
+    #[arg(short = 'a', long = "download-archive", global = true)]
+    archive: Option<String>,
+
+    /// (Optional) Proxy of all requests, http(s):// or socks5(h):// [default: None]
+    #[arg(long, global = true)]
+    proxy: Option<String>,
+
+    /// (Optional) Config file path, json, toml or yaml by extension, it replaces config file in current directory [default: None]
+    #[arg(short, long, global = true)]
+    pub config: Option<String>,
+
+    /// Output format of the download events, logs are always written to stderr
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human, global = true)]
+    output: OutputFormat,
+
+    /// Same as `--output json`
+    #[arg(long, global = true)]
+    json_lines: bool,
+
+    /// Base url of all bilibili apis, to run against a local server in tests
+    #[arg(long, global = true, hide = true)]
+    base_url: Option<String>,
+}
+
+impl GlobalArgs {
+    pub fn output_format(&self) -> OutputFormat {
+        match self.json_lines {
+            true => OutputFormat::Json,
+            false => self.output,
+        }
+    }
+}
+
+/// Inputs of the download command.
+#[derive(clap::Args, Debug, Default)]
+pub struct InputArgs {
+    /// aid/bvid, ep/ss id of a bangumi or link to download, can be multiple
+    #[arg(short, long)]
+    inputs: Option<Vec<String>>,
+
+    /// parsing a file content line by line to get input, `-` for stdin, a line can have options after the input like `name=x pages=1-3`, see README
+    #[arg(short, long)]
+    file_input: Option<String>,
+
+    /// Scan the inputs and file input as free text for every id and bilibili link, duplicates are dropped
+    #[arg(short = 'x', long)]
+    extract: bool,
+
     /// (Optional) Filename to save [default: the title of the audio]
     #[arg(short = 'o')]
     filename: Option<String>,
 
-    /// (Optional) Sessiondata for login aiming to dolby or flac [default: None]
-    #[arg(short, long)]
-    session: Option<String>,
+    /// Print the streams and output paths without downloading anything
+    #[arg(long, visible_alias = "simulate")]
+    pub dry_run: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Download audio, the default command
+    Download(InputArgs),
+    /// Print information and available audio streams of a video or a bangumi
+    Info {
+        /// aid/bvid, ep/ss id or link of the video or bangumi
+        id: String,
+    },
+    /// List videos of a collection without downloading
+    List {
+        /// up:<mid>, fav:<media_id> or series:<mid>:<series_id>
+        source: Source,
+    },
+    /// Record audio of a live room until the live ends, Ctrl-C stops it and keeps what is recorded
+    Record {
+        /// Room id or link of the live room
+        room: String,
+
+        /// (Optional) Duration to record, [[hh:]mm:]ss [default: until the live ends]
+        #[arg(short, long, value_parser = parse_duration)]
+        duration: Option<Duration>,
+
+        /// Protocol of the live stream, flv or hls
+        #[arg(long, default_value = "flv")]
+        protocol: StreamProtocol,
+
+        /// Output format, m4a or flac [default: m4a]
+        #[arg(long)]
+        format: Option<Format>,
+
+        /// (Optional) Filename to save [default: <streamer> <title> <start time>]
+        #[arg(short = 'o')]
+        filename: Option<String>,
+    },
+    /// Poll sources on an interval and download new audio
+    Watch {
+        /// Sources to watch: up:<mid>, fav:<media_id> or series:<mid>:<series_id>, can be multiple
+        #[arg(short = 'S', long = "source")]
+        sources: Option<Vec<Source>>,
 
-    /// (Optional) Config file path
-    #[arg(short, long, default_value = "./config.json")]
-    config: String,
+        /// Seconds to wait between two polls [default: 600]
+        #[arg(short = 'I', long)]
+        interval: Option<u64>,
+    },
+    /// Login by scanning QR code and save the session to config file
+    Login,
+    /// Remove the saved session from config file
+    Logout,
+    /// Show or initialize config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
 }
 
-fn error_input() -> ! {
-    panic!("\"inputs\" and \"file input\" are all empty, just add at least one of them to run, or try -h/--help for help");
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Print the effective config
+    Show {
+        /// Also print where each item comes from: default, file, env or args
+        #[arg(long)]
+        sources: bool,
+        /// Print session, tokens and proxy auth in clear text instead of masked
+        #[arg(long)]
+        show_secrets: bool,
+    },
+    /// Write the effective config merged from all sources to a json file
+    Save {
+        /// File to write
+        file: String,
+    },
+    /// Write a commented starter config file
+    Init {
+        /// Overwrite the config file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the JSON Schema of config files for editor completion
+    Schema,
 }
 
-fn get_bv<U>(link: U) -> Option<String>
-where
-    U: IntoUrl,
-{
-    let link = link.into_url();
-    if link.is_err() {
-        return None;
-    }
-    match link.unwrap().path_segments() {
-        Some(mut paths) => paths.find(|id| is_id(id)).map(|id| id.to_string()),
-        None => None,
+impl Args {
+    /// Inputs to download, `None` for the commands which download nothing from inputs.
+    pub fn download_inputs(&self) -> Option<&InputArgs> {
+        match &self.command {
+            None => Some(&self.inputs),
+            Some(Command::Download(inputs)) => Some(inputs),
+            _ => None,
+        }
     }
 }
 
-static PARSE_CILENT: Lazy<Client> = Lazy::new(|| {
-    blocking::Client::builder()
-        .redirect(Policy::none())
-        .build()
-        .unwrap()
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    parse_time(duration)
+        .filter(|seconds| *seconds > 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| format!("'{duration}' is illegal, expect [[hh:]mm:]ss like 1:30:00"))
+}
+
+/// Exit with the code of invalid input, it is called while config is being loaded.
+fn error_input() -> ! {
+    error!("\"inputs\" and \"file input\" are all empty, just add at least one of them to run, or try -h/--help for help");
+    std::process::exit(exit_code(ErrorKind::InvalidInput));
+}
+
+/// Exit when the client can't be built, e.g. the proxy is illegal.
+fn exit_client_error(e: vl::Error) -> ! {
+    error!("Failed to build http client: {e}");
+    std::process::exit(exit_code(e.kind()));
+}
+
+/// Client to resolve short links, it follows no redirection so that every hop is checked.
+static RESOLVE_CLIENT: Lazy<VlClient> = Lazy::new(|| {
+    let config = ClientConfig {
+        max_redirects: 0,
+        ..client_config(&CONFIG)
+    };
+    VlClient::with_config(&config).unwrap_or_else(|e| exit_client_error(e))
 });
 
-fn parse_link(link: Url) -> Option<String> {
-    match link.host_str() {
-        Some("www.bilibili.com") => get_bv(link),
-        Some("b23.tv") => {
-            match PARSE_CILENT.head(link).send().map(|response| {
-                let headers = response.headers();
-                if let Some(link_str) = headers.get(header::LOCATION) {
-                    let link_str = link_str.to_str().unwrap();
-                    get_bv(link_str)
-                } else {
-                    None
-                }
-            }) {
-                Ok(res) => res,
-                Err(_) => None,
+pub static ARGS: Lazy<Args> = Lazy::new(Args::parse);
+
+/// Item of a resolved input, the page of a link is kept unless pages are given.
+///
+/// Live rooms are not items, they are recorded by the record command.
+fn target_item(target: Target, mut options: ItemOptions) -> vl::Result<Item> {
+    match target {
+        Target::Video { id, page } => {
+            if options.pages.is_none() {
+                options.pages = page.map(Pages::one);
             }
+            Ok(Item::new(id.bvid(), options))
         }
-        _ => None,
+        Target::Bangumi(id) => Ok(Item::new(id.to_string(), options)),
+        Target::Live(_) => Err(vl::Error::InvalidInput(format!(
+            "{target} is a live room, record it by the record command."
+        ))),
     }
 }
 
-pub static CONFIG: Lazy<ConfigItems> = Lazy::new(|| {
-    let args = Args::parse();
+/// Room id of a live room given as a bare number or a link.
+pub async fn parse_room(input: &str) -> vl::Result<u64> {
+    if let Ok(room_id) = input.trim().parse::<u64>() {
+        return Ok(room_id);
+    }
+    match resolve(&RESOLVE_CLIENT, input).await? {
+        Target::Live(room_id) => Ok(room_id),
+        target => Err(vl::Error::InvalidInput(format!(
+            "Input '{input}' leads to {target}, which is not a live room."
+        ))),
+    }
+}
+
+/// Resolve an aid/bvid/link into the item of the canonical bvid, or the `ep`/`ss` id of a bangumi.
+pub async fn parse_input(input: &str) -> vl::Result<Item> {
+    let target = resolve(&RESOLVE_CLIENT, input).await?;
+    target_item(target, ItemOptions::default())
+}
 
-    if args.inputs.is_none() && args.file_input.is_none() {
+/// Inputs of args, the input file and config, links are resolved later by [`items`].
+fn read_inputs(args: &InputArgs, ids: Vec<String>) -> Vec<Item> {
+    if args.inputs.is_none() && args.file_input.is_none() && ids.is_empty() {
         error_input();
     }
 
-    let mut pre_inputs = match args.inputs {
-        Some(arg_inputs) => arg_inputs,
-        None => Vec::new(),
-    };
+    let mut pre_inputs: Vec<Item> = ids.into_iter().map(Item::from).collect();
+    let inputs = args.inputs.clone().unwrap_or_default();
+    match args.extract {
+        true => pre_inputs.extend(
+            inputs
+                .iter()
+                .flat_map(|text| extract_inputs(text))
+                .map(Item::from),
+        ),
+        false => pre_inputs.extend(inputs.into_iter().map(Item::from)),
+    }
 
-    if let Some(file_input) = args.file_input {
+    if let Some(file_input) = &args.file_input {
         info!("Starting to get file input");
-        let file_inputs = match read_file_string(&file_input) {
-            Ok(content) => content
-                .split('\n')
-                .filter(|s| !s.is_empty())
-                .map(|s| s.trim().to_owned())
-                .collect(),
-            Err(e) => {
-                error!("{e}");
-                vec![]
+        match read_input_string(file_input) {
+            Ok(content) if args.extract => {
+                pre_inputs.extend(extract_inputs(&content).into_iter().map(Item::from))
+            }
+            Ok(content) => {
+                let (items, errors) = input::parse_file(&content);
+                let source = match file_input.as_str() {
+                    "-" => "stdin",
+                    file => file,
+                };
+                for e in errors {
+                    error!("Parsing {source} failed at {e}, skip it");
+                }
+                pre_inputs.extend(items);
             }
+            Err(e) => error!("{e}"),
         };
-        pre_inputs.extend(file_inputs);
     };
 
     let pre_cnt = pre_inputs.len();
     info!("Input total: {pre_cnt}");
     pre_inputs.is_empty().then(error_input);
+    pre_inputs
+}
 
+/// Resolve the inputs concurrently, illegal inputs are skipped.
+async fn resolve_items(pre_inputs: Vec<Item>) -> Vec<Item> {
     info!("Starting to parse inputs");
 
-    let mut res_inputs = Vec::<String>::new();
-
-    for pre_input in pre_inputs {
-        match is_id(&pre_input) {
-            true => res_inputs.push(pre_input),
-            false => {
-                if let Ok(link) = is_link(&pre_input) {
-                    if link.scheme() == "http" || link.scheme() == "https" {
-                        if let Some(res) = parse_link(link) {
-                            res_inputs.push(res);
-                            continue;
-                        }
-                    }
-                }
-                error!("Parsing {} failed, skip it", pre_input);
+    let inputs = pre_inputs
+        .iter()
+        .map(|item| item.id.as_str())
+        .collect::<Vec<_>>();
+    let targets = resolve_all(&RESOLVE_CLIENT, &inputs).await;
+    let mut res_inputs = Vec::<Item>::new();
+
+    for (pre_input, target) in pre_inputs.iter().zip(targets) {
+        match target.and_then(|target| target_item(target, pre_input.options.clone())) {
+            // The same video may be given as av, BV or link, or mentioned more than once in text.
+            Ok(item) if res_inputs.contains(&item) => {
+                info!("Skip {} which is a duplicate of {}", pre_input.id, item.id)
             }
-        };
+            Ok(item) => res_inputs.push(item),
+            Err(e) => error!("Parsing {} failed, skip it: {e}", pre_input.id),
+        }
     }
 
     let res_cnt = res_inputs.len();
     info!("Succeed to parse: {res_cnt}");
     res_inputs.is_empty().then(error_input);
 
-    ConfigBuilder::default()
-        // Parsing file
-        .add_file(&args.config)
-        // Parsing args
-        .dolby_allowed(args.dolby_allowed)
-        .flac_allowed(args.flac_allowed)
-        .pic_allowed(args.picture_allowed)
-        .filename(args.filename)
-        .path(args.path)
-        .session(args.session)
-        .id(Some(res_inputs))
-        .build()
+    res_inputs
+}
+
+/// Inputs with their options, set by [`CONFIG_BUILDER`] before links are resolved.
+static INPUT_ITEMS: OnceCell<Vec<Item>> = OnceCell::new();
+
+/// Items to download, the ids of config have default options.
+pub async fn items() -> Vec<Item> {
+    match INPUT_ITEMS.get() {
+        Some(items) => resolve_items(items.clone()).await,
+        None => CONFIG.id().into_iter().map(Item::from).collect(),
+    }
+}
+
+/// Config files to merge from low to high precedence: system, user and project.
+///
+/// The file given by `-c` takes the place of the project one.
+pub static CONFIG_FILES: Lazy<Vec<String>> = Lazy::new(|| {
+    let mut files = config_dirs();
+    let project = files.pop();
+    let mut files = files
+        .iter()
+        .filter_map(|dir| find_config(dir))
+        .collect::<Vec<_>>();
+    match &ARGS.global.config {
+        // A missing file fails when reading, only `config init` doesn't read it.
+        Some(config) => files.push(config.clone()),
+        None => files.extend(project.and_then(|dir| find_config(&dir))),
+    }
+    files
 });
 
-pub static SESSION: Lazy<HeaderMap> = Lazy::new(|| {
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        header::COOKIE,
-        HeaderValue::from_str(&format!("SESSDATA={}", CONFIG.session())).unwrap(),
-    );
-    headers
+/// Config file written by login, logout and `config init`.
+///
+/// The file given by `-c`, or the merged one with highest precedence except the system one.
+pub static CONFIG_FILE: Lazy<String> = Lazy::new(|| {
+    let system = config_dirs().remove(0);
+    match &ARGS.global.config {
+        Some(config) => config.clone(),
+        None => CONFIG_FILES
+            .iter()
+            .rev()
+            .find(|file| !PathBuf::from(file).starts_with(&system))
+            .cloned()
+            .unwrap_or_else(|| "./config.json".to_string()),
+    }
 });
+
+/// Items of config files, the profile, env and args, the later ones take precedence.
+///
+/// Fallback profiles are given by `profile`, they don't take the account in env and args.
+fn config_builder(profile: Option<&str>) -> anyhow::Result<ConfigBuilder> {
+    let global = &ARGS.global;
+    load_env_file();
+    let mut env = ConfigBuilder::from_env()?;
+    // Parsing files
+    let mut builder = ConfigBuilder::default();
+    for file in CONFIG_FILES.iter() {
+        builder = builder.add_file(file)?;
+    }
+    if profile.is_some() {
+        env.builder_items.session = None;
+        env.builder_items.cookies = None;
+        env.builder_items.refresh_token = None;
+        env.builder_items.bili_jct = None;
+    }
+    // The profile in args overrides the one in env and config file, and its items
+    // override env. Env overrides the items of a profile chosen in env or config file.
+    let env = match profile
+        .map(str::to_string)
+        .or_else(|| global.profile.clone())
+    {
+        Some(name) => {
+            env.builder_items.profile = None;
+            builder.merge(env);
+            builder.profile(Some(name));
+            None
+        }
+        None => {
+            builder.profile(env.builder_items.profile.clone());
+            Some(env)
+        }
+    };
+    let name = builder.builder_items.profile.clone().unwrap_or_default();
+    if !name.is_empty() {
+        builder = builder.add_profile(&CONFIG_FILES, &name)?;
+    }
+    // Parsing env
+    if let Some(env) = env {
+        builder.merge(env);
+    }
+    // Parsing args
+    builder
+        .dolby_allowed(global.dolby_allowed)
+        .flac_allowed(global.flac_allowed)
+        .pic_allowed(global.picture_allowed)
+        .path(global.path.clone())
+        .archive(global.archive.clone())
+        .proxy(global.proxy.clone())
+        .profile(profile.map(str::to_string));
+    if profile.is_none() {
+        builder
+            .session(global.session.clone())
+            .cookies(global.cookies.clone());
+    }
+    if builder.builder_items.id.is_none() {
+        builder.id(Some(vec![]));
+    }
+    Ok(builder)
+}
+
+/// Items merged from all sources, with where each item comes from.
+pub static CONFIG_BUILDER: Lazy<ConfigBuilder> = Lazy::new(|| {
+    let args = &*ARGS;
+
+    let (watch_sources, watch_interval) = match &args.command {
+        Some(Command::Watch { sources, interval }) => {
+            (sources.clone().map(WatchSources), *interval)
+        }
+        _ => (None, None),
+    };
+
+    let mut builder = config_builder(None).unwrap_or_else(|e| {
+        error!("{e:#}");
+        std::process::exit(exit_code(ErrorKind::InvalidInput));
+    });
+    builder
+        .watch_sources(watch_sources)
+        .watch_interval(watch_interval);
+    // Only the commands saving audio files need the save path.
+    let unused: &[&str] = match &args.command {
+        None | Some(Command::Download(_) | Command::Watch { .. } | Command::Record { .. }) => &[],
+        _ => &["path"],
+    };
+    if let Err(e) = builder.validate_except(unused) {
+        e.to_string().lines().for_each(|line| error!("{line}"));
+        std::process::exit(exit_code(ErrorKind::InvalidInput));
+    }
+
+    // Only the download command needs inputs, ids in env or config file are taken as inputs too.
+    if let Some(inputs) = args.download_inputs() {
+        let ids = builder.builder_items.id.clone().unwrap_or_default();
+        let items = read_inputs(inputs, ids);
+        builder.filename(inputs.filename.clone());
+        let _ = INPUT_ITEMS.set(items);
+    }
+    builder
+});
+
+pub static CONFIG: Lazy<ConfigItems> = Lazy::new(|| CONFIG_BUILDER.build());
+
+/// Split a comma separated list, empty entries are dropped.
+pub fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+pub fn client_config(config: &ConfigItems) -> ClientConfig {
+    let non_empty = |s: String| Some(s).filter(|s| !s.is_empty());
+    let seconds = |s: u64| Some(Duration::from_secs(s)).filter(|_| s > 0);
+    ClientConfig {
+        proxy: ProxyConfig {
+            all: non_empty(config.proxy()),
+            api: non_empty(config.api_proxy()),
+            cdn: non_empty(config.cdn_proxy()),
+            auth: non_empty(config.proxy_auth()),
+            no_proxy: split_list(&config.no_proxy()),
+        },
+        connect_timeout: seconds(config.connect_timeout()),
+        read_timeout: seconds(config.read_timeout()),
+        http_version: config.http_version(),
+        max_redirects: config.max_redirects(),
+        tls_backend: config.tls_backend(),
+        ca_files: split_list(&config.ca_file())
+            .into_iter()
+            .map(PathBuf::from)
+            .collect(),
+        ca_only: config.ca_only(),
+        cookies: None,
+    }
+}
+
+/// Path of a cookies file, its cookies and the format to write them back.
+type CookieFile = (String, Arc<CookieJar>, CookieFormat);
+
+/// Cookies files loaded in this run.
+static COOKIE_FILES: Lazy<Mutex<Vec<CookieFile>>> = Lazy::new(Default::default);
+
+fn load_cookies(path: &str) -> vl::Result<Arc<CookieJar>> {
+    if path.is_empty() {
+        return Ok(Arc::default());
+    }
+    let (jar, format) = match std::path::Path::new(path).exists() {
+        true => {
+            let (jar, format) = CookieJar::load(path)?;
+            info!("Using cookies file {path}");
+            (Arc::new(jar), format)
+        }
+        // A new file is created once cookies are set, e.g. by login.
+        false => match path.ends_with(".json") {
+            true => (Arc::default(), CookieFormat::Json),
+            false => (Arc::default(), CookieFormat::Netscape),
+        },
+    };
+    COOKIE_FILES
+        .lock()
+        .unwrap()
+        .push((path.to_string(), Arc::clone(&jar), format));
+    Ok(jar)
+}
+
+/// Write cookies rotated by the server back to their cookies files.
+pub fn save_cookies() {
+    for (path, jar, format) in COOKIE_FILES.lock().unwrap().iter() {
+        if !jar.changed() {
+            continue;
+        }
+        match jar.save(path, *format) {
+            Ok(()) => info!("Cookies are updated to {path}"),
+            Err(e) => warn!("Failed to update cookies file {path}: {e}"),
+        }
+    }
+}
+
+/// Client with the cookies of a config, the session overrides `SESSDATA` of cookies.
+fn context(config: &ConfigItems) -> vl::Result<Context> {
+    let cookies = load_cookies(&config.cookies())?;
+    let client = VlClient::with_config(&ClientConfig {
+        cookies: Some(cookies),
+        ..client_config(config)
+    })?;
+    let mut ctx = Context::new(client);
+    if let Some(base) = &ARGS.global.base_url {
+        ctx = ctx.with_base(base);
+    }
+    if !config.session().is_empty() {
+        ctx.set_session(&config.session())?;
+    }
+    if !config.bili_jct().is_empty() {
+        ctx.set_csrf(&config.bili_jct())?;
+    }
+    Ok(ctx)
+}
+
+/// Client and base urls shared by all commands.
+pub static CONTEXT: Lazy<Context> =
+    Lazy::new(|| context(&CONFIG).unwrap_or_else(|e| exit_client_error(e)));
+
+/// Contexts of fallback profiles, built once.
+static PROFILE_CONTEXTS: Lazy<Mutex<HashMap<String, Context>>> = Lazy::new(Default::default);
+
+/// Context with the account of a profile.
+pub fn profile_context(name: &str) -> anyhow::Result<Context> {
+    let mut contexts = PROFILE_CONTEXTS.lock().unwrap();
+    if let Some(ctx) = contexts.get(name) {
+        return Ok(ctx.clone());
+    }
+    let builder = config_builder(Some(name))?;
+    builder.validate()?;
+    let ctx = context(&builder.build())?;
+    contexts.insert(name.to_string(), ctx.clone());
+    Ok(ctx)
+}
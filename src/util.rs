@@ -1,16 +1,16 @@
 #![allow(dead_code)]
 
 use anyhow::{Context, Result};
+use log::warn;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use reqwest::Url;
 use std::{fs::File, io::Read, path::Path, str::FromStr};
+use vl::id::{BangumiId, VideoId};
+use vl::resolve::{parse_link, Target, SHORT_HOSTS};
 
 pub fn get_env_str(name: &str) -> Option<String> {
-    match std::env::var(name) {
-        Ok(value) => Some(value),
-        Err(_) => None,
-    }
+    std::env::var(name).ok()
 }
 
 pub fn get_env_bool(name: &str) -> Option<bool> {
@@ -19,7 +19,11 @@ pub fn get_env_bool(name: &str) -> Option<bool> {
     match get_env_str(name) {
         Some(str_res) if TRUE_VALUES.contains(&str_res.to_lowercase().as_ref()) => Some(true),
         Some(str_res) if FALSE_VALUES.contains(&str_res.to_lowercase().as_ref()) => Some(false),
-        _ => None,
+        Some(str_res) => {
+            warn!("Ignore env {name}={str_res}, it should be true or false");
+            None
+        }
+        None => None,
     }
 }
 
@@ -29,13 +33,61 @@ where
 {
     match get_env_str(name) {
         Some(str_res) => match str_res.parse::<V>() {
-            Ok(res) => Some(res),
-            Err(_) => None,
+            Ok(value) => Some(value),
+            Err(_) => {
+                warn!("Ignore env {name}={str_res}, the value is illegal");
+                None
+            }
         },
         None => None,
     }
 }
 
+/// Split by commas or whitespaces, empty entries are dropped.
+pub fn get_env_list(name: &str) -> Option<Vec<String>> {
+    get_env_str(name).map(|str_res| {
+        str_res
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+/// Config values which can be read from an environment variable.
+pub trait FromEnv: Sized {
+    fn from_env(name: &str) -> Option<Self>;
+}
+
+impl FromEnv for bool {
+    fn from_env(name: &str) -> Option<Self> {
+        get_env_bool(name)
+    }
+}
+
+impl FromEnv for Vec<String> {
+    fn from_env(name: &str) -> Option<Self> {
+        get_env_list(name)
+    }
+}
+
+/// Read the type from env by its `FromStr`.
+macro_rules! impl_from_env {
+    ($($ty:ty),+) => {
+        $(
+            impl $crate::util::FromEnv for $ty {
+                fn from_env(name: &str) -> Option<Self> {
+                    $crate::util::get_env(name)
+                }
+            }
+        )+
+    };
+}
+
+pub(crate) use impl_from_env;
+
+impl_from_env!(String, u64, usize);
+
 pub fn read_file_string(path: &str) -> Result<String> {
     let mut contents = String::new();
     let mut file = File::open(Path::new(path))?;
@@ -43,32 +95,108 @@ pub fn read_file_string(path: &str) -> Result<String> {
     Ok(contents)
 }
 
-pub fn is_link(input: &str) -> Result<Url> {
-    Url::parse(input).context("input is not a link")
+/// Read a file, or stdin when the path is `-`.
+pub fn read_input_string(path: &str) -> Result<String> {
+    if path != "-" {
+        return read_file_string(path);
+    }
+    let mut contents = String::new();
+    std::io::stdin()
+        .read_to_string(&mut contents)
+        .context("Read stdin failed.")?;
+    Ok(contents)
 }
 
-static SAFE_FILENAME: Lazy<Regex> = Lazy::new(|| Regex::new(r#"[?|_|*|<|>|\|、|/|"]"#).unwrap());
-
-pub fn safe_filename(filename: &str) -> String {
-    SAFE_FILENAME.replace_all(filename, " ").to_string()
+/// Whether the input is an aid/bvid or the `ep`/`ss` id of a bangumi.
+pub fn is_id(input: &str) -> bool {
+    input.parse::<VideoId>().is_ok() || input.parse::<BangumiId>().is_ok()
 }
 
-static IS_ID: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(bv.{10}|(av)?\d{1,10})$").unwrap());
+/// Bilibili links with their query, short links and BV/av ids in free text.
+static EMBEDDED_INPUT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(concat!(
+        r"(?i)(?:https?://)?(?:(?:www|m|live)\.)?bilibili\.com/[0-9a-z\-._~/?#&=%+]*",
+        r"|(?:https?://)?(?:b23\.tv|bili2233\.cn|bili22\.cn|bili33\.cn)/[0-9a-z]+",
+        r"|(?-u:\b)(?:bv[0-9a-z]{10}|av\d{1,16})(?-u:\b)"
+    ))
+    .unwrap()
+});
 
-pub fn is_id(input: &str) -> bool {
-    IS_ID.is_match(&input.trim().to_ascii_lowercase())
+/// Every id and link in pasted text such as chat messages, html or markdown, in order without duplicates.
+///
+/// Links are kept whole for `p=` and checked by [`parse_link`], unknown pages are dropped
+/// and short links are kept to be resolved. An id of a video linked before is taken as
+/// the text of the link.
+pub fn extract_inputs(text: &str) -> Vec<String> {
+    let mut inputs = Vec::new();
+    let mut targets: Vec<Target> = Vec::new();
+    for found in EMBEDDED_INPUT.find_iter(text) {
+        let input = found.as_str().trim_end_matches('.');
+        let input = match input.contains('/') && !input.contains("://") {
+            true => format!("https://{input}"),
+            false => input.to_string(),
+        };
+        let url = Url::parse(&input).ok();
+        let target = match input.parse::<VideoId>() {
+            Ok(id) => Some(Target::Video { id, page: None }),
+            Err(_) => url.as_ref().and_then(parse_link),
+        };
+        let duplicate = match target {
+            Some(target) => targets.iter().any(|seen| match (seen, target) {
+                (
+                    Target::Video { id, .. },
+                    Target::Video {
+                        id: linked,
+                        page: None,
+                    },
+                ) => *id == linked,
+                (seen, target) => *seen == target,
+            }),
+            None => {
+                let short = url
+                    .as_ref()
+                    .and_then(Url::host_str)
+                    .is_some_and(|host| SHORT_HOSTS.contains(&host));
+                !short || inputs.contains(&input)
+            }
+        };
+        if !duplicate {
+            targets.extend(target);
+            inputs.push(input);
+        }
+    }
+    inputs
 }
 
 #[test]
-fn test_filename() {
-    assert_eq!(
-        "ABC123 q w e r t y u i o".to_string(),
-        safe_filename("ABC123?q_w*e<r>t|y\"u、i/o")
-    );
+fn test_url() {
+    let url = reqwest::Url::parse("http://evil.com1232\\@i.ibb.co/1.png").unwrap();
+    println!("{url:?}");
 }
 
 #[test]
-fn test_url() {
-    let url = Url::parse("http://evil.com1232\\@i.ibb.co/1.png").unwrap();
-    println!("{url:?}");
+fn extract_inputs_test() {
+    let text =
+        "看看这个<a href=\"https://www.bilibili.com/video/BV1u8411H7yA/?p=2\">BV1u8411H7yA</a>\n\
+        [short](https://b23.tv/7ebAgB2) and b23.tv/abcDEF1，还有av170001的\n\
+        m.bilibili.com/video/av600924585 JAVA123 xBV1rR4y1D7di BV138411j7bi。\n\
+        OP 在 https://www.bilibili.com/bangumi/play/ep693247?from=x 里，\
+        直播间 live.bilibili.com/21452505?spm_id=1. 和 www.bilibili.com/read/cv1 \
+        <https://www.bilibili.com/festival/2023bnj?bvid=BV1Bk4y1E7zy&p=3>";
+    assert_eq!(
+        extract_inputs(text),
+        vec![
+            "https://www.bilibili.com/video/BV1u8411H7yA/?p=2",
+            "https://b23.tv/7ebAgB2",
+            "https://b23.tv/abcDEF1",
+            "av170001",
+            "https://m.bilibili.com/video/av600924585",
+            "BV138411j7bi",
+            "https://www.bilibili.com/bangumi/play/ep693247?from=x",
+            "https://live.bilibili.com/21452505?spm_id=1",
+            "https://www.bilibili.com/festival/2023bnj?bvid=BV1Bk4y1E7zy&p=3"
+        ]
+    );
+    assert!(is_id("av170001") && is_id("BV17x411w7KC") && !is_id("av0"));
+    assert!(is_id("ss41410") && !is_id("md28339"));
 }
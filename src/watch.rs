@@ -0,0 +1,127 @@
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use vl::catcher::collection::{self, Source};
+use vl::item::Item;
+
+use crate::parse::{CONFIG, CONTEXT};
+use crate::{command, downloader, open_archive, run_items};
+
+/// Archive used by watch mode when no download archive is configured.
+static DEFAULT_ARCHIVE: &str = "voiceload.archive";
+
+/// Set on Ctrl-C, the downloader stops taking new ids once it is set.
+pub static SHUTDOWN: Lazy<Arc<AtomicBool>> = Lazy::new(Default::default);
+static SHUTDOWN_NOTIFY: Lazy<Notify> = Lazy::new(Notify::new);
+
+/// Sources list in config, split by ',' when given as a single string.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct WatchSources(pub Vec<Source>);
+
+impl FromStr for WatchSources {
+    type Err = vl::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(Source::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .map(WatchSources)
+    }
+}
+
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::SeqCst)
+}
+
+/// The first Ctrl-C stops after the current work, the second one exits at once.
+///
+/// Listening replaces the default handler, so a stuck download could not be interrupted otherwise.
+pub fn listen_shutdown() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if shutdown_requested() {
+                warn!("Received Ctrl-C again, exit now");
+                // 128 + SIGINT, as killed by the default handler.
+                std::process::exit(130);
+            }
+            warn!("Received Ctrl-C, finishing current work before exit, press Ctrl-C again to force exit");
+            SHUTDOWN.store(true, Ordering::SeqCst);
+            SHUTDOWN_NOTIFY.notify_one();
+        }
+    });
+}
+
+/// Fails when no source is set or the archive can't be opened, errors of a poll are only logged.
+pub async fn watch() -> anyhow::Result<()> {
+    let sources = CONFIG.watch_sources().0;
+    if sources.is_empty() {
+        return Err(vl::Error::InvalidInput(
+            "No watch sources, set them by -S/--source or \"watch_sources\" in config file"
+                .to_string(),
+        )
+        .into());
+    }
+
+    // The archive keeps what has been downloaded between restarts.
+    let archive_name = match CONFIG.archive().is_empty() {
+        true => DEFAULT_ARCHIVE.to_string(),
+        false => CONFIG.archive(),
+    };
+    let downloader = match open_archive(&archive_name)? {
+        Some(archive) => downloader(Some(archive)),
+        None => unreachable!(),
+    };
+
+    let interval = Duration::from_secs(CONFIG.watch_interval());
+    listen_shutdown();
+
+    loop {
+        // Cookies may expire during a long watch.
+        command::refresh_session().await;
+        let mut new_ids = Vec::<String>::new();
+        for source in &sources {
+            match collection::api(&CONTEXT, source, None).await {
+                Ok(items) => {
+                    let archive = downloader.archive().unwrap().lock().unwrap();
+                    for item in items {
+                        if !archive.contains_bvid(&item.bvid) && !new_ids.contains(&item.bvid) {
+                            new_ids.push(item.bvid);
+                        }
+                    }
+                }
+                Err(e) => error!("Failed to poll {source}: {e}"),
+            }
+        }
+
+        info!(
+            "Found {} new audio from {} sources",
+            new_ids.len(),
+            sources.len()
+        );
+        if !new_ids.is_empty() {
+            // Failed items are not archived, so they are retried in the next poll.
+            let items = new_ids.into_iter().map(Item::from).collect::<Vec<_>>();
+            run_items(&downloader, &items).await;
+        }
+
+        if shutdown_requested() {
+            break;
+        }
+
+        info!("Next poll in {:?}", interval);
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = SHUTDOWN_NOTIFY.notified() => break,
+        }
+    }
+
+    info!("Watch mode stopped");
+    Ok(())
+}
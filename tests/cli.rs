@@ -0,0 +1,127 @@
+//! The command line against a local mock server with the recorded responses of vl.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn fixture(name: &str, server: &MockServer) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("vl/tests/fixtures")
+        .join(name);
+    std::fs::read_to_string(path)
+        .unwrap()
+        .replace("{base}", &server.uri())
+}
+
+/// View and playurl of BV12g411r7mB, the view response rotates a cookie.
+async fn mock_video(server: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path("/x/web-interface/view"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .append_header("Set-Cookie", "buvid3=new_buvid; Path=/")
+                .set_body_raw(fixture("view.json", server), "application/json"),
+        )
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/x/player/playurl"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_raw(fixture("playurl.json", server), "application/json"),
+        )
+        .mount(server)
+        .await;
+}
+
+/// A config with an account due to be refreshed and a cookies file, in a new directory.
+fn account_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("voiceload-cli-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("cookies.txt"),
+        "127.0.0.1\tFALSE\t/\tFALSE\t0\tbuvid3\told_buvid\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("config.json"),
+        r#"{"session":"old_session","bili_jct":"old_csrf","refresh_token":"old_token","cookies":"cookies.txt"}"#,
+    )
+    .unwrap();
+    dir
+}
+
+fn voiceload(dir: &Path, server: &MockServer, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_voiceload"))
+        .current_dir(dir)
+        .args(["-c", "config.json", "--base-url", &server.uri()])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dry_run_test() {
+    let server = MockServer::start().await;
+    mock_video(&server).await;
+    // The session is due to be refreshed, which a dry run must not do.
+    Mock::given(path("/x/passport-login/web/cookie/info"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_raw(fixture("cookie_info.json", &server), "application/json"),
+        )
+        .expect(0)
+        .mount(&server)
+        .await;
+    let dir = account_dir("dry-run");
+    let files = |dir: &Path| {
+        ["config.json", "cookies.txt"].map(|name| std::fs::read_to_string(dir.join(name)).unwrap())
+    };
+    let before = files(&dir);
+
+    let output = voiceload(
+        &dir,
+        &server,
+        &["download", "--dry-run", "-i", "BV12g411r7mB"],
+    );
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Selected: [30280]"), "{stdout}");
+    assert_eq!(files(&dir), before);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+    // Pages selecting nothing fail like downloading does.
+    std::fs::write(dir.join("ids.txt"), "BV12g411r7mB pages=9\n").unwrap();
+    let output = voiceload(&dir, &server, &["download", "--dry-run", "-f", "ids.txt"]);
+    assert_eq!(output.status.code(), Some(13), "{output:?}");
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Selected:"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn json_dry_run_test() {
+    let server = MockServer::start().await;
+    mock_video(&server).await;
+    let dir = account_dir("json");
+
+    let args = [
+        "--json-lines",
+        "download",
+        "--dry-run",
+        "-i",
+        "BV12g411r7mB",
+    ];
+    let output = voiceload(&dir, &server, &args);
+    assert!(output.status.success(), "{output:?}");
+    // Stdout has json events only, the text for human goes to stderr.
+    let events = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .map(|event| event["event"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(events, ["metadata_fetched", "stream_selected"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Selected: [30280]"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
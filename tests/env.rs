@@ -0,0 +1,95 @@
+//! Config from env, in its own binary so the variables are only set for the child process.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Items of `config show --sources`, as name => (value, source).
+fn config_show(
+    dir: &PathBuf,
+    envs: &[(&str, &str)],
+    args: &[&str],
+) -> HashMap<String, (String, String)> {
+    let output = Command::new(env!("CARGO_BIN_EXE_voiceload"))
+        .current_dir(dir)
+        .envs(envs.iter().copied())
+        .args(["-c", "config.json"])
+        .args(args)
+        .args(["config", "show", "--sources", "--show-secrets"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (name, rest) = line
+                .strip_prefix("\x1b[32m[*]\x1b[0m ")?
+                .split_once(" => ")?;
+            let (value, source) = rest.split_once(" \x1b[90m(")?;
+            let source = source.strip_suffix(")\x1b[0m")?;
+            Some((name.to_string(), (value.to_string(), source.to_string())))
+        })
+        .collect()
+}
+
+fn config_dir(name: &str, config: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("voiceload-env-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("config.json"), config).unwrap();
+    dir
+}
+
+#[test]
+fn env_test() {
+    let dir = config_dir(
+        "items",
+        r#"{"max_redirects":10,"pic_allowed":false,"proxy":"http://127.0.0.1:8080"}"#,
+    );
+    let envs = [
+        ("VOICELOAD_ID", "BV12g411r7mB, av170001 BV17x411w7KC"),
+        ("VOICELOAD_PIC_ALLOWED", "Yes"),
+        ("VOICELOAD_MAX_REDIRECTS", "3"),
+        ("VOICELOAD_HTTP_VERSION", "http2"),
+        ("VOICELOAD_PROXY", "http://127.0.0.1:8081"),
+    ];
+    let items = config_show(&dir, &envs, &["--proxy", "http://127.0.0.1:8082"]);
+    assert_eq!(
+        items["id"].0,
+        r#"["BV12g411r7mB","av170001","BV17x411w7KC"]"#
+    );
+    assert_eq!(
+        items["pic_allowed"],
+        ("true".into(), "env:VOICELOAD_PIC_ALLOWED".into())
+    );
+    assert_eq!(items["http_version"].0, r#""http2""#);
+
+    // Env overrides file, args override env.
+    assert_eq!(items["max_redirects"].0, "3");
+    assert_eq!(
+        items["proxy"],
+        (r#""http://127.0.0.1:8082""#.into(), "args".into())
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn env_profile_test() {
+    let dir = config_dir(
+        "profile",
+        r#"{"session":"file","profiles":{"vip":{"session":"vip"},"bulk":{"session":"bulk"}}}"#,
+    );
+    let envs = [("VOICELOAD_SESSION", "env")];
+    let session =
+        |envs: &[(&str, &str)], args: &[&str]| config_show(&dir, envs, args)["session"].0.clone();
+    assert_eq!(session(&envs, &[]), r#""env""#);
+
+    // A profile selected in args overrides env.
+    assert_eq!(session(&envs, &["--profile", "vip"]), r#""vip""#);
+    let envs = [("VOICELOAD_SESSION", "env"), ("VOICELOAD_PROFILE", "bulk")];
+    assert_eq!(session(&envs, &["--profile", "vip"]), r#""vip""#);
+
+    // Env overrides a profile selected in env.
+    assert_eq!(session(&envs, &[]), r#""env""#);
+    assert_eq!(session(&envs[1..], &[]), r#""bulk""#);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
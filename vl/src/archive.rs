@@ -0,0 +1,161 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::id::VideoId;
+use crate::Result;
+
+/// One finished download, stored as a tab separated line:
+/// `bvid  cid  quality  size  timestamp  path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    // BV 号
+    pub bvid: String,
+    // 分P的cid
+    pub cid: usize,
+    // 音频流的质量代码
+    pub quality: usize,
+    // 输出文件大小(字节)
+    pub size: u64,
+    // 完成时间(unix 秒)
+    pub timestamp: u64,
+    // 输出文件路径
+    pub path: String,
+}
+
+impl Record {
+    pub fn new(bvid: &str, cid: usize, quality: usize, path: &Path) -> Self {
+        let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Record {
+            bvid: bvid.to_string(),
+            cid,
+            quality,
+            size,
+            timestamp,
+            path: path.to_string_lossy().to_string(),
+        }
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.bvid, self.cid, self.quality, self.size, self.timestamp, self.path
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(6, '\t');
+        Some(Record {
+            bvid: fields.next()?.to_string(),
+            cid: fields.next()?.parse().ok()?,
+            quality: fields.next()?.parse().ok()?,
+            size: fields.next()?.parse().ok()?,
+            timestamp: fields.next()?.parse().ok()?,
+            path: fields.next()?.to_string(),
+        })
+    }
+}
+
+/// A line based download archive, like youtube-dl's `--download-archive`.
+///
+/// Videos are looked up by [`VideoId`], so a bvid and its aid are the same video.
+pub struct Archive {
+    path: PathBuf,
+    done: HashSet<(VideoId, usize)>,
+}
+
+impl Archive {
+    /// Load the archive file, an absent file is treated as an empty archive.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut done = HashSet::new();
+
+        if path.exists() {
+            let file = File::open(&path)?;
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                let line = line.trim_end_matches('\r');
+                if line.is_empty() {
+                    continue;
+                }
+                match Record::from_line(line).and_then(|r| Some((r.bvid.parse().ok()?, r.cid))) {
+                    Some(key) => {
+                        done.insert(key);
+                    }
+                    None => log::warn!("Skip broken archive line: {line}"),
+                }
+            }
+        }
+
+        Ok(Archive { path, done })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.done.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.done.is_empty()
+    }
+
+    pub fn contains(&self, bvid: &str, cid: usize) -> bool {
+        match bvid.parse::<VideoId>() {
+            Ok(id) => self.done.contains(&(id, cid)),
+            Err(_) => false,
+        }
+    }
+
+    /// Whether any page of the video has been downloaded.
+    pub fn contains_bvid(&self, bvid: &str) -> bool {
+        match bvid.parse::<VideoId>() {
+            Ok(id) => self.done.iter().any(|(done, _)| *done == id),
+            Err(_) => false,
+        }
+    }
+
+    /// Append a record to the archive file.
+    pub fn add(&mut self, record: Record) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", record.to_line())?;
+        if let Ok(id) = record.bvid.parse() {
+            self.done.insert((id, record.cid));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn archive_test() {
+    let path = std::env::temp_dir().join(format!("vl-archive-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut archive = Archive::open(&path).unwrap();
+    assert!(archive.is_empty());
+    archive
+        .add(Record::new("BV12g411r7mB", 123, 30280, Path::new("a.m4a")))
+        .unwrap();
+    assert!(archive.contains("BV12g411r7mB", 123));
+
+    let archive = Archive::open(&path).unwrap();
+    assert_eq!(archive.len(), 1);
+    assert!(archive.contains("BV12g411r7mB", 123));
+    assert!(!archive.contains("BV12g411r7mB", 456));
+    let aid = "BV12g411r7mB".parse::<VideoId>().unwrap().aid();
+    assert!(archive.contains(&format!("av{aid}"), 123));
+    assert!(archive.contains_bvid(&aid.to_string()));
+
+    let _ = std::fs::remove_file(&path);
+}
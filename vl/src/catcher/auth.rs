@@ -1 +1,265 @@
+use once_cell::sync::Lazy;
+use openssl::encrypt::Encrypter;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rsa::Padding;
+use regex::Regex;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
 
+use super::{
+    get, post, Response, API_CONFIRM_REFRESH, API_COOKIE_INFO, API_COOKIE_REFRESH, API_CORRESPOND,
+    API_QRCODE_GENERATE, API_QRCODE_POLL,
+};
+use crate::{Context, Error, Result};
+
+// 生成 correspond path 的公钥
+static CORRESPOND_KEY: &str = "-----BEGIN PUBLIC KEY-----
+MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQDLgd2OAkcGVtoE3ThUREbio0Eg
+Uc/prcajMKXvkCKFCWhJYJcLkcM2DKKcSeFpD/j6Boy538YXnR6VhcuUJOhH2x71
+nzPjfdTcqMz7djHum0qSZA0AyCBDABUqCrfNgCiJ00Ra7GmRj+YCK1NJEuewlb40
+JNrRuoEUXpabUzGB8QIDAQAB
+-----END PUBLIC KEY-----";
+
+static REFRESH_CSRF: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"<div id="1-name">\s*([^<\s]+)\s*</div>"#).unwrap());
+
+#[derive(Deserialize, Debug)]
+pub struct QrCode {
+    // 二维码内容
+    pub url: String,
+    // 扫码登录秘钥
+    pub qrcode_key: String,
+}
+
+#[derive(Serialize, Debug)]
+struct PollReq<'a> {
+    qrcode_key: &'a str,
+}
+
+#[derive(Deserialize, Debug)]
+struct PollRsp {
+    // 刷新 cookie 用的 refresh_token
+    refresh_token: String,
+    // 扫码状态码
+    code: isize,
+    message: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum QrStatus {
+    /// 未扫码
+    Waiting,
+    /// 已扫码未确认
+    Scanned,
+    /// 二维码已失效
+    Expired,
+    /// 登录成功
+    Confirmed {
+        session: String,
+        // bili_jct, 刷新 cookie 时用作 csrf
+        csrf: String,
+        refresh_token: String,
+    },
+}
+
+/// Apply for a login QR code.
+pub async fn qrcode_generate(ctx: &Context) -> Result<QrCode> {
+    get(ctx, &ctx.passport(API_QRCODE_GENERATE), &(), None).await
+}
+
+/// Poll the scanning status, the session and csrf are taken from `Set-Cookie` once confirmed.
+pub async fn qrcode_poll(ctx: &Context, qrcode_key: &str) -> Result<QrStatus> {
+    let poll_req = PollReq { qrcode_key };
+    let response = ctx
+        .client
+        .request(Method::GET, ctx.passport(API_QRCODE_POLL), &poll_req, None)
+        .await?;
+
+    let cookie = |name: &str| {
+        response
+            .cookies()
+            .find(|cookie| cookie.name() == name)
+            .map(|cookie| cookie.value().to_string())
+            .ok_or_else(|| Error::Parse(format!("No {name} in login response.")))
+    };
+    let (session, csrf) = (cookie("SESSDATA"), cookie("bili_jct"));
+
+    let bytes = response.bytes().await?;
+    let data: PollRsp = serde_json::from_slice::<Response>(&bytes[..])?.into_data()?;
+
+    match data.code {
+        0 => Ok(QrStatus::Confirmed {
+            session: session?,
+            csrf: csrf?,
+            refresh_token: data.refresh_token,
+        }),
+        86101 => Ok(QrStatus::Waiting),
+        86090 => Ok(QrStatus::Scanned),
+        86038 => Ok(QrStatus::Expired),
+        code => Err(Error::ApiCode {
+            code,
+            message: data.message,
+        }),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CookieInfo {
+    // 是否需要刷新
+    pub refresh: bool,
+    // 当前毫秒时间戳, 用于生成 correspond path
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct CsrfReq<'a> {
+    csrf: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+struct RefreshReq<'a> {
+    csrf: &'a str,
+    refresh_csrf: &'a str,
+    source: &'a str,
+    refresh_token: &'a str,
+}
+
+#[derive(Deserialize, Debug)]
+struct RefreshRsp {
+    refresh_token: String,
+}
+
+#[derive(Serialize, Debug)]
+struct ConfirmReq<'a> {
+    csrf: &'a str,
+    refresh_token: &'a str,
+}
+
+/// Session after refreshing, the other cookies are updated in the cookie store.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Refreshed {
+    pub session: String,
+    pub csrf: String,
+    pub refresh_token: String,
+}
+
+/// `bili_jct` cookie, the csrf token of all post apis.
+fn csrf(ctx: &Context) -> Result<String> {
+    ctx.client
+        .cookies()
+        .get("bili_jct")
+        .ok_or_else(|| Error::NeedLogin("No bili_jct in cookies.".to_string()))
+}
+
+/// Check whether the cookies need refreshing.
+pub async fn cookie_info(ctx: &Context) -> Result<CookieInfo> {
+    let csrf = csrf(ctx)?;
+    get(
+        ctx,
+        &ctx.passport(API_COOKIE_INFO),
+        &CsrfReq { csrf: &csrf },
+        None,
+    )
+    .await
+}
+
+/// Encrypt `refresh_{timestamp}` by RSA-OAEP, the result differs every time.
+pub fn correspond_path(timestamp: u64) -> Result<String> {
+    let encrypt = || -> std::result::Result<Vec<u8>, openssl::error::ErrorStack> {
+        let key = PKey::public_key_from_pem(CORRESPOND_KEY.as_bytes())?;
+        let mut encrypter = Encrypter::new(&key)?;
+        encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+        encrypter.set_rsa_oaep_md(MessageDigest::sha256())?;
+        let plain = format!("refresh_{timestamp}");
+        let mut encrypted = vec![0; encrypter.encrypt_len(plain.as_bytes())?];
+        let len = encrypter.encrypt(plain.as_bytes(), &mut encrypted)?;
+        encrypted.truncate(len);
+        Ok(encrypted)
+    };
+    let encrypted =
+        encrypt().map_err(|e| Error::Parse(format!("Failed to encrypt correspond path: {e}")))?;
+    Ok(encrypted.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// Get the refresh_csrf from the correspond page.
+pub async fn refresh_csrf(ctx: &Context, correspond_path: &str) -> Result<String> {
+    let url = ctx.www(&format!("{API_CORRESPOND}{correspond_path}"));
+    let response = ctx
+        .client
+        .request(Method::GET, url, &(), None)
+        .await?
+        .error_for_status()?;
+    let html = ctx.client.timed(response.text()).await??;
+    REFRESH_CSRF
+        .captures(&html)
+        .map(|caps| caps[1].to_string())
+        .ok_or_else(|| Error::Parse("No refresh_csrf in correspond page.".to_string()))
+}
+
+/// Exchange the refresh token for new cookies, returns the new refresh token.
+pub async fn cookie_refresh(
+    ctx: &Context,
+    refresh_csrf: &str,
+    refresh_token: &str,
+) -> Result<String> {
+    let csrf = csrf(ctx)?;
+    let refresh_req = RefreshReq {
+        csrf: &csrf,
+        refresh_csrf,
+        source: "main_web",
+        refresh_token,
+    };
+    let data: RefreshRsp = post(ctx, &ctx.passport(API_COOKIE_REFRESH), &refresh_req).await?;
+    Ok(data.refresh_token)
+}
+
+/// Invalidate the old refresh token with the new csrf.
+pub async fn confirm_refresh(ctx: &Context, old_refresh_token: &str) -> Result<()> {
+    let csrf = csrf(ctx)?;
+    let confirm_req = ConfirmReq {
+        csrf: &csrf,
+        refresh_token: old_refresh_token,
+    };
+    ctx.client
+        .post_form::<_, _, Response>(ctx.passport(API_CONFIRM_REFRESH), &confirm_req, None)
+        .await?
+        .check()
+}
+
+/// Run the whole refresh flow if needed, `None` when the cookies are still fresh.
+pub async fn refresh_cookies(ctx: &Context, refresh_token: &str) -> Result<Option<Refreshed>> {
+    let info = cookie_info(ctx).await?;
+    if !info.refresh {
+        return Ok(None);
+    }
+
+    let refresh_csrf = refresh_csrf(ctx, &correspond_path(info.timestamp)?).await?;
+    let new_token = cookie_refresh(ctx, &refresh_csrf, refresh_token).await?;
+    confirm_refresh(ctx, refresh_token).await?;
+
+    let session = ctx
+        .client
+        .cookies()
+        .get("SESSDATA")
+        .ok_or_else(|| Error::Parse("No SESSDATA in refresh response.".to_string()))?;
+    Ok(Some(Refreshed {
+        session,
+        csrf: csrf(ctx)?,
+        refresh_token: new_token,
+    }))
+}
+
+#[test]
+fn correspond_path_test() {
+    let path = correspond_path(1684466082000).unwrap();
+    assert_eq!(path.len(), 256);
+    assert!(path.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_ne!(path, correspond_path(1684466082000).unwrap());
+
+    let html = r#"<div id="1-name">b0cc8411ded2f9db2cff2edb3123acac</div>"#;
+    assert_eq!(
+        &REFRESH_CSRF.captures(html).unwrap()[1],
+        "b0cc8411ded2f9db2cff2edb3123acac"
+    );
+}
@@ -0,0 +1,212 @@
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use super::{get, get_wbi, API_FAV_LIST, API_SERIES_ARCHIVES, API_SPACE_ARC};
+use crate::{Context, Error, Result};
+
+/// A collection of videos which can be expanded into bvids.
+/// - `up:<mid>` 投稿视频
+/// - `fav:<media_id>` 收藏夹
+/// - `series:<mid>:<series_id>` 视频列表
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub enum Source {
+    Uploader(usize),
+    Favorite(usize),
+    Series(usize, usize),
+}
+
+impl FromStr for Source {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let illegal = || {
+            Error::InvalidInput(format!(
+                "Source '{s}' is illegal, expect up:<mid>, fav:<media_id> or series:<mid>:<series_id>."
+            ))
+        };
+        let id = |id: &str| id.parse::<usize>().map_err(|_| illegal());
+        let parts: Vec<&str> = s.trim().split(':').collect();
+        match parts[..] {
+            ["up", mid] => Ok(Source::Uploader(id(mid)?)),
+            ["fav", media_id] => Ok(Source::Favorite(id(media_id)?)),
+            ["series", mid, series_id] => Ok(Source::Series(id(mid)?, id(series_id)?)),
+            _ => Err(illegal()),
+        }
+    }
+}
+
+impl TryFrom<String> for Source {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<Source> for String {
+    fn from(source: Source) -> Self {
+        source.to_string()
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Uploader(mid) => write!(f, "up:{mid}"),
+            Source::Favorite(media_id) => write!(f, "fav:{media_id}"),
+            Source::Series(mid, series_id) => write!(f, "series:{mid}:{series_id}"),
+        }
+    }
+}
+
+static PAGE_SIZE: usize = 30;
+// 收藏夹每页最多 20 条
+static FAV_PAGE_SIZE: usize = 20;
+
+#[derive(Deserialize, Debug)]
+pub struct Item {
+    // BV 号
+    pub bvid: String,
+    // 稿件标题
+    pub title: String,
+}
+
+#[derive(Serialize, Debug)]
+struct SpaceReq {
+    mid: usize,
+    ps: usize,
+    pn: usize,
+    order: &'static str,
+}
+
+#[derive(Deserialize, Debug)]
+struct SpacePage {
+    count: usize,
+}
+
+#[derive(Deserialize, Debug)]
+struct SpaceList {
+    vlist: Vec<Item>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SpaceRsp {
+    list: SpaceList,
+    page: SpacePage,
+}
+
+#[derive(Serialize, Debug)]
+struct FavReq {
+    media_id: usize,
+    ps: usize,
+    pn: usize,
+    platform: &'static str,
+}
+
+#[derive(Deserialize, Debug)]
+struct FavRsp {
+    medias: Option<Vec<Item>>,
+    has_more: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct SeriesReq {
+    mid: usize,
+    series_id: usize,
+    ps: usize,
+    pn: usize,
+    sort: &'static str,
+}
+
+#[derive(Deserialize, Debug)]
+struct SeriesPage {
+    total: usize,
+}
+
+#[derive(Deserialize, Debug)]
+struct SeriesRsp {
+    archives: Option<Vec<Item>>,
+    page: SeriesPage,
+}
+
+/// Fetch one page of the source, returns the items and whether more pages exist.
+async fn page(
+    ctx: &Context,
+    source: &Source,
+    pn: usize,
+    headers: Option<HeaderMap>,
+) -> Result<(Vec<Item>, bool)> {
+    match *source {
+        Source::Uploader(mid) => {
+            let req = SpaceReq {
+                mid,
+                ps: PAGE_SIZE,
+                pn,
+                order: "pubdate",
+            };
+            let data: SpaceRsp = get_wbi(ctx, &ctx.api(API_SPACE_ARC), &req, headers).await?;
+            let more = pn * PAGE_SIZE < data.page.count;
+            Ok((data.list.vlist, more))
+        }
+        Source::Favorite(media_id) => {
+            let req = FavReq {
+                media_id,
+                ps: FAV_PAGE_SIZE,
+                pn,
+                platform: "web",
+            };
+            let data: FavRsp = get(ctx, &ctx.api(API_FAV_LIST), &req, headers).await?;
+            let more = data.has_more;
+            Ok((data.medias.unwrap_or_default(), more))
+        }
+        Source::Series(mid, series_id) => {
+            let req = SeriesReq {
+                mid,
+                series_id,
+                ps: PAGE_SIZE,
+                pn,
+                sort: "desc",
+            };
+            let data: SeriesRsp = get(ctx, &ctx.api(API_SERIES_ARCHIVES), &req, headers).await?;
+            let more = pn * PAGE_SIZE < data.page.total;
+            Ok((data.archives.unwrap_or_default(), more))
+        }
+    }
+}
+
+/// Expand a source into all of its items, newest first.
+pub async fn api(ctx: &Context, source: &Source, headers: Option<HeaderMap>) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+    let mut pn = 1;
+    loop {
+        let (page_items, more) = page(ctx, source, pn, headers.clone()).await?;
+        let empty = page_items.is_empty();
+        items.extend(page_items);
+        if !more || empty {
+            break;
+        }
+        pn += 1;
+    }
+    Ok(items)
+}
+
+#[test]
+fn source_test() {
+    assert_eq!(
+        "up:946974".parse::<Source>().unwrap(),
+        Source::Uploader(946974)
+    );
+    assert_eq!(
+        "fav:1052622027".parse::<Source>().unwrap(),
+        Source::Favorite(1052622027)
+    );
+    assert_eq!(
+        "series:946974:1891".parse::<Source>().unwrap(),
+        Source::Series(946974, 1891)
+    );
+    assert!("series:946974".parse::<Source>().is_err());
+    assert_eq!(Source::Series(1, 2).to_string(), "series:1:2");
+}
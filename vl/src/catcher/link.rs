@@ -1,11 +1,9 @@
-use anyhow::Result;
 use reqwest::header::HeaderMap;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
-use crate::common::CLIENT;
+use crate::{Context, Error, Result};
 
-use super::{Response, API_PLAYURL};
+use super::{get, null_to_default, API_PLAYURL};
 
 #[derive(Serialize, Debug)]
 struct LinkReq<'a> {
@@ -14,6 +12,18 @@ struct LinkReq<'a> {
     fnval: usize,
 }
 
+/// Human readable name of an audio quality code.
+pub fn quality_name(id: usize) -> &'static str {
+    match id {
+        30216 => "64K",
+        30232 => "132K",
+        30280 => "192K",
+        30250 => "Dolby",
+        30251 => "Hi-Res",
+        _ => "Unknown",
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Audio {
     pub id: usize,
@@ -21,11 +31,14 @@ pub struct Audio {
     pub backup_url: Vec<String>,
     pub mime_type: String,
     pub codecs: String,
+    pub bandwidth: usize,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Dolby {
-    pub audio: Value,
+    #[serde(rename = "type")]
+    pub kind: usize,
+    pub audio: Option<Vec<Audio>>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -35,6 +48,7 @@ pub struct Flac {
 
 #[derive(Deserialize, Debug)]
 pub struct Dash {
+    #[serde(default, deserialize_with = "null_to_default")]
     pub audio: Vec<Audio>,
     pub dolby: Option<Dolby>,
     pub flac: Option<Flac>,
@@ -46,24 +60,33 @@ pub struct LinkRsp {
 }
 
 pub async fn api(
+    ctx: &Context,
     bvid: &str,
     cid: usize,
     fnval: usize,
     headers: Option<HeaderMap>,
 ) -> Result<LinkRsp> {
     let link_req = LinkReq { bvid, cid, fnval };
-    let response = CLIENT
-        .get_struct::<_, _, Response<LinkRsp>>(API_PLAYURL, &link_req, headers)
-        .await
-        .unwrap();
-    Ok(response.data)
+    let data: LinkRsp = get(ctx, &ctx.api(API_PLAYURL), &link_req, headers).await?;
+
+    match data.dash.audio.is_empty() {
+        true => Err(Error::NoAudioStream),
+        false => Ok(data),
+    }
 }
 
 #[tokio::test]
+#[ignore = "needs network, covered offline in tests/catcher.rs"]
 async fn api_test() {
-    let res = api("BV1fB4y1h76Z", 773130617, 16 | 256, None)
-        .await
-        .unwrap();
+    let res = api(
+        &Context::default(),
+        "BV1fB4y1h76Z",
+        773130617,
+        16 | 256,
+        None,
+    )
+    .await
+    .unwrap();
     // for x in res.dash.audio {
     //     let code = x.id;
     //     let url = x.base_url;
@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+
+use super::{get, null_to_default, API_LIVE_PLAY_INFO, API_LIVE_ROOM_INFO};
+use crate::loader::{LiveStream, StreamProtocol};
+use crate::{Context, Error, Result};
+
+#[derive(Serialize, Debug)]
+struct RoomInfoReq {
+    room_id: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct PlayInfoReq {
+    room_id: u64,
+    // 0 为 http_stream (flv), 1 为 http_hls
+    protocol: &'static str,
+    // 0 为 flv, 1 为 ts, 2 为 fmp4
+    format: &'static str,
+    // 0 为 avc, 1 为 hevc
+    codec: &'static str,
+    // 10000 为原画
+    qn: usize,
+    platform: &'static str,
+    ptype: usize,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RoomInfo {
+    // 真实房间号, 请求时可以是短号
+    pub room_id: u64,
+    // 主播 mid
+    pub uid: u64,
+    // 直播间标题
+    pub title: String,
+    // 0 未开播, 1 直播中, 2 轮播中
+    pub live_status: usize,
+    // 直播间封面图片url
+    #[serde(default)]
+    pub cover: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct BaseInfo {
+    // 主播名字
+    #[serde(default)]
+    pub uname: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct AnchorInfo {
+    #[serde(default, deserialize_with = "null_to_default")]
+    pub base_info: BaseInfo,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RoomRsp {
+    pub room_info: RoomInfo,
+    #[serde(default, deserialize_with = "null_to_default")]
+    pub anchor_info: AnchorInfo,
+}
+
+impl RoomRsp {
+    pub fn is_live(&self) -> bool {
+        self.room_info.live_status == 1
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct UrlInfo {
+    host: String,
+    #[serde(default)]
+    extra: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Codec {
+    // avc 或 hevc
+    codec_name: String,
+    base_url: String,
+    #[serde(default, deserialize_with = "null_to_default")]
+    url_info: Vec<UrlInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Format {
+    // flv、ts 或 fmp4
+    format_name: String,
+    #[serde(default, deserialize_with = "null_to_default")]
+    codec: Vec<Codec>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Stream {
+    // http_stream 或 http_hls
+    protocol_name: String,
+    #[serde(default, deserialize_with = "null_to_default")]
+    format: Vec<Format>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Playurl {
+    #[serde(default, deserialize_with = "null_to_default")]
+    stream: Vec<Stream>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct PlayurlInfo {
+    #[serde(default, deserialize_with = "null_to_default")]
+    playurl: Playurl,
+}
+
+#[derive(Deserialize, Debug)]
+struct PlayInfoRsp {
+    live_status: usize,
+    // 未开播时为 null
+    #[serde(default, deserialize_with = "null_to_default")]
+    playurl_info: PlayurlInfo,
+}
+
+impl PlayInfoRsp {
+    /// Url of the protocol, avc is preferred and ts is preferred over fmp4 for hls.
+    fn select(&self, protocol: StreamProtocol) -> Option<LiveStream> {
+        let protocol_name = match protocol {
+            StreamProtocol::Flv => "http_stream",
+            StreamProtocol::Hls => "http_hls",
+        };
+        let formats = self
+            .playurl_info
+            .playurl
+            .stream
+            .iter()
+            .filter(|stream| stream.protocol_name == protocol_name)
+            .flat_map(|stream| &stream.format);
+        let (format, codec) = formats
+            .flat_map(|format| format.codec.iter().map(move |codec| (format, codec)))
+            .filter(|(_, codec)| !codec.url_info.is_empty())
+            .min_by_key(|(format, codec)| {
+                (codec.codec_name != "avc", format.format_name != "ts")
+            })?;
+        let url_info = &codec.url_info[0];
+
+        Some(LiveStream {
+            url: format!("{}{}{}", url_info.host, codec.base_url, url_info.extra),
+            protocol,
+            extension: match format.format_name.as_str() {
+                "fmp4" => "m4s".to_string(),
+                name => name.to_string(),
+            },
+        })
+    }
+}
+
+/// Info of a live room, the room id can be the short one.
+pub async fn room_info(ctx: &Context, room_id: u64) -> Result<RoomRsp> {
+    let room_info_req = RoomInfoReq { room_id };
+
+    get(ctx, &ctx.live(API_LIVE_ROOM_INFO), &room_info_req, None).await
+}
+
+/// Stream url of a live room, fails with [`Error::NotFound`] when the room is not live
+/// and [`Error::NoAudioStream`] when no stream of the protocol is given.
+///
+/// Urls expire in about an hour, get a new one when reconnecting.
+pub async fn stream(ctx: &Context, room_id: u64, protocol: StreamProtocol) -> Result<LiveStream> {
+    let play_info_req = PlayInfoReq {
+        room_id,
+        protocol: "0,1",
+        format: "0,1,2",
+        codec: "0,1",
+        qn: 10000,
+        platform: "web",
+        ptype: 8,
+    };
+    let data: PlayInfoRsp = get(ctx, &ctx.live(API_LIVE_PLAY_INFO), &play_info_req, None).await?;
+
+    if data.live_status != 1 {
+        return Err(Error::NotFound(format!("Room {room_id} is not live.")));
+    }
+    // Hls streams are missing sometimes in the first minutes of a live.
+    data.select(protocol).ok_or(Error::NoAudioStream)
+}
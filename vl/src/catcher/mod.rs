@@ -1,15 +1,177 @@
-use serde::Deserialize;
+use reqwest::header::HeaderMap;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::{Context, Error, Result};
 
 pub mod auth;
+pub mod collection;
 pub mod link;
+pub mod live;
+pub mod pgc;
 pub mod view;
 
-pub static API_VIEW: &str = "http://api.bilibili.com/x/web-interface/view";
-pub static API_PLAYURL: &str = "http://api.bilibili.com/x/player/playurl";
+// Paths relative to `Context::api_base`.
+pub static API_VIEW: &str = "/x/web-interface/view";
+pub static API_PLAYURL: &str = "/x/player/playurl";
+pub static API_PGC_SEASON: &str = "/pgc/view/web/season";
+pub static API_PGC_PLAYURL: &str = "/pgc/player/web/playurl";
+pub static API_SPACE_ARC: &str = "/x/space/wbi/arc/search";
+pub static API_FAV_LIST: &str = "/x/v3/fav/resource/list";
+pub static API_SERIES_ARCHIVES: &str = "/x/series/archives";
+// Paths relative to `Context::passport_base`.
+pub static API_QRCODE_GENERATE: &str = "/x/passport-login/web/qrcode/generate";
+pub static API_QRCODE_POLL: &str = "/x/passport-login/web/qrcode/poll";
+pub static API_COOKIE_INFO: &str = "/x/passport-login/web/cookie/info";
+pub static API_COOKIE_REFRESH: &str = "/x/passport-login/web/cookie/refresh";
+pub static API_CONFIRM_REFRESH: &str = "/x/passport-login/web/confirm/refresh";
+// Paths relative to `Context::www_base`.
+pub static API_CORRESPOND: &str = "/correspond/1/";
+// Paths relative to `Context::live_base`.
+pub static API_LIVE_ROOM_INFO: &str = "/xlive/web-room/v1/index/getInfoByRoom";
+pub static API_LIVE_PLAY_INFO: &str = "/xlive/web-room/v2/index/getRoomPlayInfo";
+
+/// Treat `null` as the default value, api returns `null` for empty lists.
+fn null_to_default<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
 
+/// Common wrapper of all api responses.
+///
+/// `data` is kept as json until `code` is checked, as failed responses carry
+/// `null` or something else in it. PGC apis name it `result`.
 #[derive(Deserialize, Debug)]
-struct Response<T> {
+struct Response {
     code: isize,
+    #[serde(default)]
     message: String,
-    data: T,
+    #[serde(default, alias = "result")]
+    data: Option<Value>,
+}
+
+impl Response {
+    /// Check the code only, for apis returning no data.
+    fn check(self) -> Result<()> {
+        match self.code {
+            0 => Ok(()),
+            code => Err(Error::from_code(code, self.message)),
+        }
+    }
+
+    /// Check the code and deserialize the data.
+    fn into_data<T: DeserializeOwned>(self) -> Result<T> {
+        if self.code != 0 {
+            return Err(Error::from_code(self.code, self.message));
+        }
+        match self.data {
+            None | Some(Value::Null) => Err(Error::Parse("Response data is null.".to_string())),
+            Some(data) => Ok(serde_json::from_value(data)?),
+        }
+    }
+}
+
+/// Send a GET request to the api and unwrap the data of the response.
+async fn get<P, T>(ctx: &Context, url: &str, params: &P, headers: Option<HeaderMap>) -> Result<T>
+where
+    P: Serialize + ?Sized,
+    T: DeserializeOwned,
+{
+    ctx.client
+        .get_struct::<_, _, Response>(url, params, headers)
+        .await?
+        .into_data()
+}
+
+/// Post a form to the api and unwrap the data of the response.
+async fn post<F, T>(ctx: &Context, url: &str, form: &F) -> Result<T>
+where
+    F: Serialize + ?Sized,
+    T: DeserializeOwned,
+{
+    ctx.client
+        .post_form::<_, _, Response>(url, form, None)
+        .await?
+        .into_data()
+}
+
+/// Flatten params into query pairs, `null` values are dropped.
+fn query_pairs<P: Serialize + ?Sized>(params: &P) -> Result<Vec<(String, String)>> {
+    match serde_json::to_value(params)? {
+        Value::Null => Ok(vec![]),
+        Value::Object(map) => Ok(map
+            .into_iter()
+            .filter(|(_, v)| !v.is_null())
+            .map(|(k, v)| match v {
+                Value::String(s) => (k, s),
+                v => (k, v.to_string()),
+            })
+            .collect()),
+        _ => Err(Error::Parse("Params must be a struct or map.".to_string())),
+    }
+}
+
+/// Same as [`get`] for apis which need wbi signing.
+///
+/// The mixin key may have rotated when the request is rejected, so it is
+/// refetched and the request is retried once.
+async fn get_wbi<P, T>(
+    ctx: &Context,
+    url: &str,
+    params: &P,
+    headers: Option<HeaderMap>,
+) -> Result<T>
+where
+    P: Serialize + ?Sized,
+    T: DeserializeOwned,
+{
+    let pairs = query_pairs(params)?;
+    let signed = ctx.wbi_sign(pairs.clone()).await?;
+    match get(ctx, url, &signed, headers.clone()).await {
+        Err(Error::RiskControl(_)) => {
+            ctx.wbi_invalidate();
+            let signed = ctx.wbi_sign(pairs).await?;
+            get(ctx, url, &signed, headers).await
+        }
+        result => result,
+    }
+}
+
+#[test]
+fn response_test() {
+    let response: Response =
+        serde_json::from_str(r#"{"code":-404,"message":"啥都木有","ttl":1}"#).unwrap();
+    assert!(matches!(
+        response.into_data::<Value>(),
+        Err(Error::NotFound(_))
+    ));
+
+    let response: Response =
+        serde_json::from_str(r#"{"code":0,"message":"0","data":null}"#).unwrap();
+    assert!(matches!(
+        response.into_data::<Value>(),
+        Err(Error::Parse(_))
+    ));
+
+    let response: Response =
+        serde_json::from_str(r#"{"code":-10403,"message":"抱歉您所在地区不可观看！","data":{}}"#)
+            .unwrap();
+    assert!(matches!(
+        response.into_data::<Value>(),
+        Err(Error::Forbidden(_))
+    ));
+
+    let response: Response =
+        serde_json::from_str(r#"{"code":0,"message":"0","data":{"bvid":"BV12g411r7mB"}}"#).unwrap();
+    let data: Value = response.into_data().unwrap();
+    assert_eq!(data["bvid"], "BV12g411r7mB");
+
+    let response: Response =
+        serde_json::from_str(r#"{"code":0,"message":"success","result":{"season_id":41410}}"#)
+            .unwrap();
+    let data: Value = response.into_data().unwrap();
+    assert_eq!(data["season_id"], 41410);
 }
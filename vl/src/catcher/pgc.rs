@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+use super::link::LinkRsp;
+use super::{get, null_to_default, API_PGC_PLAYURL, API_PGC_SEASON};
+use crate::id::BangumiId;
+use crate::item::Pages;
+use crate::{Context, Error, Result};
+
+#[derive(Serialize, Debug)]
+struct SeasonReq {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ep_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    season_id: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+struct PlayurlReq {
+    ep_id: u64,
+    cid: usize,
+    fnval: usize,
+    fourk: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Episode {
+    // ep id
+    pub id: u64,
+    // 对应稿件的 AV 号
+    pub aid: usize,
+    // 对应稿件的 BV 号
+    pub bvid: String,
+    // 对应稿件的 cid
+    pub cid: usize,
+    // 集数, 如 1、SP
+    pub title: String,
+    // 单集标题
+    #[serde(default)]
+    pub long_title: String,
+    // 单集封面图片url
+    pub cover: String,
+    // 时长, 单位毫秒
+    #[serde(default)]
+    pub duration: usize,
+}
+
+impl Episode {
+    /// Title of the episode, the index when it has no title.
+    pub fn name(&self) -> &str {
+        match self.long_title.is_empty() {
+            true => &self.title,
+            false => &self.long_title,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Section {
+    // 分区标题, 如 PV&其他
+    pub title: String,
+    // 分区中的 OP、ED、PV 等
+    #[serde(default, deserialize_with = "null_to_default")]
+    pub episodes: Vec<Episode>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct UpInfo {
+    // 出品方名字
+    #[serde(default)]
+    pub uname: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SeasonRsp {
+    // ss id
+    pub season_id: u64,
+    // 剧集标题
+    pub title: String,
+    // 剧集封面图片url
+    pub cover: String,
+    // 出品方信息
+    #[serde(default, deserialize_with = "null_to_default")]
+    pub up_info: UpInfo,
+    // 正片列表
+    #[serde(default, deserialize_with = "null_to_default")]
+    pub episodes: Vec<Episode>,
+    // 正片以外的分区
+    #[serde(default, deserialize_with = "null_to_default")]
+    pub section: Vec<Section>,
+}
+
+impl SeasonRsp {
+    /// Episodes of the id with their track numbers from 1.
+    ///
+    /// An episode id selects that episode, which can also be an OP/ED in sections.
+    /// A season id selects the main episodes in `pages`, all of them by default.
+    pub fn select(&self, id: BangumiId, pages: Option<&Pages>) -> Result<Vec<(usize, &Episode)>> {
+        let selected = match id {
+            BangumiId::Episode(ep_id) => std::iter::once(&self.episodes)
+                .chain(self.section.iter().map(|section| &section.episodes))
+                .find_map(|episodes| {
+                    let index = episodes.iter().position(|episode| episode.id == ep_id)?;
+                    Some(vec![(index + 1, &episodes[index])])
+                })
+                .unwrap_or_default(),
+            BangumiId::Season(_) => pages
+                .cloned()
+                .unwrap_or_else(Pages::all)
+                .select(self.episodes.len())
+                .into_iter()
+                .map(|track| (track, &self.episodes[track - 1]))
+                .collect(),
+        };
+        match selected.is_empty() {
+            true => Err(Error::NotFound(format!(
+                "No episode of {id} is selected in {} of {} episodes",
+                self.title,
+                self.episodes.len()
+            ))),
+            false => Ok(selected),
+        }
+    }
+}
+
+/// Season of a bangumi, both an episode id and a season id give the whole season.
+pub async fn season(ctx: &Context, id: BangumiId) -> Result<SeasonRsp> {
+    let season_req = match id {
+        BangumiId::Episode(ep_id) => SeasonReq {
+            ep_id: Some(ep_id),
+            season_id: None,
+        },
+        BangumiId::Season(season_id) => SeasonReq {
+            ep_id: None,
+            season_id: Some(season_id),
+        },
+    };
+
+    get(ctx, &ctx.api(API_PGC_SEASON), &season_req, None).await
+}
+
+/// Streams of an episode, the same dash as [`super::link::api`].
+pub async fn playurl(ctx: &Context, ep_id: u64, cid: usize, fnval: usize) -> Result<LinkRsp> {
+    let playurl_req = PlayurlReq {
+        ep_id,
+        cid,
+        fnval,
+        fourk: 1,
+    };
+    let data: LinkRsp = get(ctx, &ctx.api(API_PGC_PLAYURL), &playurl_req, None).await?;
+
+    match data.dash.audio.is_empty() {
+        true => Err(Error::NoAudioStream),
+        false => Ok(data),
+    }
+}
@@ -1,36 +1,20 @@
-use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-use super::{Response, API_VIEW};
-use crate::common::CLIENT;
+use super::{get, API_VIEW};
+use crate::id::VideoId;
+use crate::{Context, Result};
 
 #[derive(Serialize, Debug)]
-pub enum BiliId<'a> {
-    #[serde(rename = "bvid")]
-    BV(&'a str),
-    #[serde(rename = "aid")]
-    AV(usize),
-}
-
-pub fn get_video_id(id: &str) -> Result<BiliId> {
-    let prefix = &id.to_lowercase()[..2];
-    match prefix {
-        "bv" => Ok(BiliId::BV(id)),
-        "av" => Ok(BiliId::AV(id[2..].parse::<usize>()?)),
-        _ => Err(anyhow::Error::msg("Avid/Bvid is illegal.")),
-    }
-}
-
-#[derive(Serialize, Debug)]
-struct ViewReq<'a> {
-    #[serde(flatten)]
-    id: BiliId<'a>,
+struct ViewReq {
+    bvid: String,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Page {
     // 分P的cid
     pub cid: usize,
+    // 分P标题
+    pub part: String,
     // 分P时长
     pub duration: usize,
 }
@@ -67,30 +51,26 @@ pub struct ViewRsp {
     pub pages: Vec<Page>,
 }
 
-pub async fn api(id: &str) -> Result<ViewRsp> {
-    let video_id = get_video_id(id)?;
-    let view_req = ViewReq { id: video_id };
-
-    let response = CLIENT
-        .get_struct::<_, _, Response<ViewRsp>>(API_VIEW, &view_req, None)
-        .await?;
-
-    if response.code != 0 {
-        return Err(anyhow::Error::msg(response.message));
-    }
+pub async fn api(ctx: &Context, id: &str) -> Result<ViewRsp> {
+    let video_id = id.parse::<VideoId>()?;
+    let view_req = ViewReq {
+        bvid: video_id.bvid(),
+    };
 
-    Ok(response.data)
+    get(ctx, &ctx.api(API_VIEW), &view_req, None).await
 }
 
 #[tokio::test]
+#[ignore = "needs network, covered offline in tests/catcher.rs"]
 async fn api_test() {
-    let res = api("BV12g411r7mB").await.unwrap();
+    let ctx = Context::default();
+    let res = api(&ctx, "BV12g411r7mB").await.unwrap();
     assert_eq!(
         &res.title,
         "【鹿乃×こはならむ】翻唱《ねぇねぇねぇ（呐呐呐。 ）》"
     );
     println!("{}", res.pic);
 
-    let res = api("av600924585").await.unwrap();
+    let res = api(&ctx, "av600924585").await.unwrap();
     assert_eq!(&res.owner.name, "影视飓风");
 }
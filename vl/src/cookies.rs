@@ -0,0 +1,328 @@
+use cookie::time::OffsetDateTime;
+use cookie::Cookie as RawCookie;
+use cookie_store::{CookieDomain, CookieExpiration, CookieStore};
+use reqwest::header::HeaderValue;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use crate::{Error, Result};
+
+/// File formats of exported cookies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieFormat {
+    /// `cookies.txt` used by curl, wget and yt-dlp.
+    Netscape,
+    /// Array exported by browser extensions such as EditThisCookie and Cookie-Editor.
+    Json,
+}
+
+impl CookieFormat {
+    /// Json exports start with `[` or `{`, anything else is read as cookies.txt.
+    pub fn detect(text: &str) -> Self {
+        match text.trim_start().chars().next() {
+            Some('[') | Some('{') => CookieFormat::Json,
+            _ => CookieFormat::Netscape,
+        }
+    }
+}
+
+fn root_path() -> String {
+    "/".to_string()
+}
+
+/// One cookie of browser extension exports, other fields are ignored.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct JsonCookie {
+    domain: String,
+    name: String,
+    value: String,
+    #[serde(default = "root_path")]
+    path: String,
+    #[serde(default)]
+    secure: bool,
+    #[serde(default)]
+    http_only: bool,
+    #[serde(default)]
+    host_only: bool,
+    // 过期时间戳 (秒), 会话 cookie 没有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expiration_date: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonExport {
+    List(Vec<JsonCookie>),
+    Wrapped { cookies: Vec<JsonCookie> },
+}
+
+impl JsonCookie {
+    fn insert(&self, store: &mut CookieStore) -> Result<()> {
+        let domain = self.domain.trim_start_matches('.');
+        let url = Url::parse(&format!("https://{domain}{}", self.path))
+            .map_err(|_| Error::Parse(format!("Cookie '{}' has illegal domain.", self.name)))?;
+        let mut builder = RawCookie::build(self.name.clone(), self.value.clone())
+            .path(self.path.clone())
+            .secure(self.secure)
+            .http_only(self.http_only);
+        if !self.host_only {
+            builder = builder.domain(domain.to_string());
+        }
+        if let Some(expires) = self.expiration_date {
+            let expires = OffsetDateTime::from_unix_timestamp(expires as i64)
+                .map_err(|_| Error::Parse(format!("Cookie '{}' has illegal expiry.", self.name)))?;
+            if expires <= OffsetDateTime::now_utc() {
+                return Ok(());
+            }
+            builder = builder.expires(expires);
+        }
+        store
+            .insert_raw(&builder.finish(), &url)
+            .map_err(|e| Error::Parse(format!("Cookie '{}' is rejected: {e}", self.name)))?;
+        Ok(())
+    }
+
+    /// Parse a line of cookies.txt, `None` for comments and blank lines.
+    fn from_netscape(line: &str) -> Option<Result<Self>> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (http_only, line) = match line.strip_prefix("#HttpOnly_") {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let fields = line.split('\t').collect::<Vec<_>>();
+        if fields.len() < 6 {
+            return Some(Err(Error::Parse(format!(
+                "Cookie line '{line}' should have 7 tab separated fields."
+            ))));
+        }
+        let expires = fields[4].parse::<f64>().unwrap_or(0.0);
+        Some(Ok(JsonCookie {
+            domain: fields[0].to_string(),
+            host_only: !fields[1].eq_ignore_ascii_case("TRUE"),
+            path: fields[2].to_string(),
+            secure: fields[3].eq_ignore_ascii_case("TRUE"),
+            expiration_date: (expires > 0.0).then_some(expires),
+            name: fields[5].to_string(),
+            value: fields.get(6).unwrap_or(&"").to_string(),
+            http_only,
+        }))
+    }
+
+    fn to_netscape(&self) -> String {
+        format!(
+            "{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            if self.http_only { "#HttpOnly_" } else { "" },
+            match self.host_only {
+                true => self.domain.clone(),
+                false => format!(".{}", self.domain),
+            },
+            if self.host_only { "FALSE" } else { "TRUE" },
+            self.path,
+            if self.secure { "TRUE" } else { "FALSE" },
+            self.expiration_date.unwrap_or(0.0) as i64,
+            self.name,
+            self.value
+        )
+    }
+}
+
+/// Cookie store of [`crate::Client`], which can be loaded from and saved to exported files.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    store: RwLock<CookieStore>,
+    // 服务端下发了新的 cookie
+    changed: AtomicBool,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(text: &str, format: CookieFormat) -> Result<Self> {
+        let cookies = match format {
+            CookieFormat::Netscape => text
+                .lines()
+                .filter_map(JsonCookie::from_netscape)
+                .collect::<Result<Vec<_>>>()?,
+            CookieFormat::Json => match serde_json::from_str::<JsonExport>(text)? {
+                JsonExport::List(cookies) | JsonExport::Wrapped { cookies } => cookies,
+            },
+        };
+
+        let mut store = CookieStore::default();
+        for cookie in &cookies {
+            cookie.insert(&mut store)?;
+        }
+        Ok(Self {
+            store: RwLock::new(store),
+            changed: AtomicBool::new(false),
+        })
+    }
+
+    /// Load an exported file, the format is detected by its content.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<(Self, CookieFormat)> {
+        let text = std::fs::read_to_string(path)?;
+        let format = CookieFormat::detect(&text);
+        Ok((Self::parse(&text, format)?, format))
+    }
+
+    fn cookies(&self) -> Vec<JsonCookie> {
+        let store = self.store.read().unwrap();
+        store
+            .iter_unexpired()
+            .filter_map(|cookie| {
+                let (domain, host_only) = match &cookie.domain {
+                    CookieDomain::HostOnly(domain) => (domain.clone(), true),
+                    CookieDomain::Suffix(domain) => (domain.clone(), false),
+                    _ => return None,
+                };
+                Some(JsonCookie {
+                    domain,
+                    host_only,
+                    name: cookie.name().to_string(),
+                    value: cookie.value().to_string(),
+                    path: cookie.path.to_string(),
+                    secure: cookie.secure().unwrap_or(false),
+                    http_only: cookie.http_only().unwrap_or(false),
+                    expiration_date: match cookie.expires {
+                        CookieExpiration::AtUtc(expires) => Some(expires.unix_timestamp() as f64),
+                        CookieExpiration::SessionEnd => None,
+                    },
+                })
+            })
+            .collect()
+    }
+
+    pub fn to_string(&self, format: CookieFormat) -> Result<String> {
+        let cookies = self.cookies();
+        match format {
+            CookieFormat::Netscape => {
+                let mut text = "# Netscape HTTP Cookie File\n".to_string();
+                for cookie in cookies {
+                    text.push_str(&cookie.to_netscape());
+                    text.push('\n');
+                }
+                Ok(text)
+            }
+            CookieFormat::Json => Ok(serde_json::to_string_pretty(&cookies)?),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: CookieFormat) -> Result<()> {
+        std::fs::write(path, self.to_string(format)?)?;
+        self.changed.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Whether the server has set new cookies since loaded or saved.
+    pub fn changed(&self) -> bool {
+        self.changed.load(Ordering::SeqCst)
+    }
+
+    /// Value of the first unexpired cookie with the name.
+    pub fn get(&self, name: &str) -> Option<String> {
+        let store = self.store.read().unwrap();
+        let value = store
+            .iter_unexpired()
+            .find(|cookie| cookie.name() == name)
+            .map(|cookie| cookie.value().to_string());
+        value
+    }
+
+    /// Set a session cookie for `domain` and its subdomains, it doesn't count as a change.
+    pub fn set(&self, domain: &str, name: &str, value: &str) -> Result<()> {
+        let cookie = JsonCookie {
+            domain: domain.to_string(),
+            name: name.to_string(),
+            value: value.to_string(),
+            path: root_path(),
+            secure: false,
+            http_only: false,
+            host_only: false,
+            expiration_date: None,
+        };
+        cookie.insert(&mut self.store.write().unwrap())
+    }
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let cookies = cookie_headers
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| RawCookie::parse(value.to_string()).ok())
+            .collect::<Vec<_>>();
+        if cookies.is_empty() {
+            return;
+        }
+
+        let mut store = self.store.write().unwrap();
+        let values = |store: &CookieStore| {
+            store
+                .iter_unexpired()
+                .map(|cookie| (cookie.name().to_string(), cookie.value().to_string()))
+                .collect::<HashSet<_>>()
+        };
+        let before = values(&store);
+        store.store_response_cookies(cookies.into_iter(), url);
+        if values(&store) != before {
+            self.changed.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let value = self
+            .store
+            .read()
+            .unwrap()
+            .get_request_values(url)
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        match value.is_empty() {
+            true => None,
+            false => HeaderValue::from_str(&value).ok(),
+        }
+    }
+}
+
+#[test]
+fn cookies_test() {
+    let text = "# Netscape HTTP Cookie File\n\
+        .bilibili.com\tTRUE\t/\tFALSE\t4102444800\tbuvid3\tABC-123\n\
+        #HttpOnly_.bilibili.com\tTRUE\t/\tTRUE\t4102444800\tSESSDATA\tsess%2Cdata\r\n\
+        .bilibili.com\tTRUE\t/\tFALSE\t1\texpired\tx\n\
+        \n";
+    let jar = CookieJar::parse(text, CookieFormat::detect(text)).unwrap();
+    assert_eq!(jar.get("SESSDATA").as_deref(), Some("sess%2Cdata"));
+    assert_eq!(jar.get("expired"), None);
+
+    let url = Url::parse("https://api.bilibili.com/x/web-interface/nav").unwrap();
+    let header = reqwest::cookie::CookieStore::cookies(&jar, &url).unwrap();
+    assert!(header.to_str().unwrap().contains("buvid3=ABC-123"));
+
+    let saved = jar.to_string(CookieFormat::Netscape).unwrap();
+    assert!(
+        saved.contains("#HttpOnly_.bilibili.com\tTRUE\t/\tTRUE\t4102444800\tSESSDATA\tsess%2Cdata")
+    );
+
+    let json = r#"[{"domain":".bilibili.com","name":"bili_jct","value":"csrf","hostOnly":false,
+        "path":"/","secure":false,"httpOnly":false,"session":true,"storeId":"0"}]"#;
+    let jar = CookieJar::parse(json, CookieFormat::detect(json)).unwrap();
+    assert_eq!(jar.get("bili_jct").as_deref(), Some("csrf"));
+    assert!(!jar.changed());
+
+    let set_cookie = HeaderValue::from_static("bili_jct=rotated; Domain=.bilibili.com; Path=/");
+    reqwest::cookie::CookieStore::set_cookies(&jar, &mut [&set_cookie].into_iter(), &url);
+    assert!(jar.changed());
+    assert_eq!(jar.get("bili_jct").as_deref(), Some("rotated"));
+}
@@ -0,0 +1,858 @@
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+use crate::archive::{Archive, Record};
+use crate::catcher::{link, live, pgc, view};
+use crate::event::Event;
+use crate::id::BangumiId;
+use crate::item::{Item, ItemOptions};
+use crate::loader::{load, load_with_progress, Progress, Recorder, StreamProtocol};
+use crate::transfer;
+use crate::{Context, Error, Result};
+
+static SAFE_FILENAME: Lazy<Regex> = Lazy::new(|| Regex::new(r#"[?|_|*|<|>|\|、|/|"]"#).unwrap());
+
+pub fn safe_filename(filename: &str) -> String {
+    SAFE_FILENAME.replace_all(filename, " ").to_string()
+}
+
+/// Audio format of the output file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    M4a,
+    Flac,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::M4a => "m4a",
+            Format::Flac => "flac",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "m4a" => Ok(Format::M4a),
+            "flac" => Ok(Format::Flac),
+            _ => Err(Error::InvalidInput(format!(
+                "Format '{s}' is illegal, expect m4a or flac."
+            ))),
+        }
+    }
+}
+
+/// What happened to an item which didn't fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Audio is written to `output`.
+    Done { output: PathBuf, size: u64 },
+    /// Audio is already recorded in the archive.
+    Skipped,
+}
+
+#[derive(Debug)]
+pub struct ItemResult {
+    pub index: usize,
+    pub id: String,
+    pub result: Result<Outcome>,
+}
+
+pub type EventHandler = Arc<dyn Fn(Event) + Send + Sync>;
+
+/// Downloads audio of videos and bangumi episodes and transforms them by ffmpeg,
+/// or records audio of live rooms.
+///
+/// ```no_run
+/// # async fn example() {
+/// let downloader = vl::downloader::Downloader::builder()
+///     .output_dir("./music")
+///     .flac_allowed(true)
+///     .session("SESSDATA")
+///     .build();
+/// let results = downloader.run(&["BV12g411r7mB".to_string()]).await;
+/// # }
+/// ```
+pub struct Downloader {
+    ctx: Context,
+    output_dir: PathBuf,
+    flac_allowed: bool,
+    pic_allowed: bool,
+    filename: Option<String>,
+    archive: Option<Arc<Mutex<Archive>>>,
+    on_event: Option<EventHandler>,
+    cancel: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+pub struct DownloaderBuilder {
+    ctx: Option<Context>,
+    output_dir: Option<PathBuf>,
+    flac_allowed: bool,
+    pic_allowed: bool,
+    filename: Option<String>,
+    session: Option<String>,
+    archive: Option<Archive>,
+    on_event: Option<EventHandler>,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+impl DownloaderBuilder {
+    /// Client and base urls of requests [default: `Context::default()`]
+    pub fn context(mut self, ctx: Context) -> Self {
+        self.ctx = Some(ctx);
+        self
+    }
+
+    /// Directory to save audio files [default: ./]
+    pub fn output_dir<P: AsRef<Path>>(mut self, output_dir: P) -> Self {
+        self.output_dir = Some(output_dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Prefer flac when the video supports it.
+    pub fn flac_allowed(mut self, flac_allowed: bool) -> Self {
+        self.flac_allowed = flac_allowed;
+        self
+    }
+
+    /// Attach the cover to the audio.
+    pub fn pic_allowed(mut self, pic_allowed: bool) -> Self {
+        self.pic_allowed = pic_allowed;
+        self
+    }
+
+    /// Fixed output file name instead of the video title, empty for none.
+    pub fn filename<S: Into<String>>(mut self, filename: S) -> Self {
+        self.filename = Some(filename.into()).filter(|s| !s.is_empty());
+        self
+    }
+
+    /// `SESSDATA` of the account, empty for none.
+    pub fn session<S: Into<String>>(mut self, session: S) -> Self {
+        self.session = Some(session.into()).filter(|s| !s.is_empty());
+        self
+    }
+
+    /// Skip audio recorded in the archive and record finished ones.
+    pub fn archive(mut self, archive: Archive) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    /// Receive events of the pipeline, including download progress.
+    pub fn on_event<F>(mut self, on_event: F) -> Self
+    where
+        F: Fn(Event) + Send + Sync + 'static,
+    {
+        self.on_event = Some(Arc::new(on_event));
+        self
+    }
+
+    /// Stop taking new items once the flag is set.
+    pub fn cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    pub fn build(self) -> Downloader {
+        let ctx = self.ctx.unwrap_or_default();
+        if let Some(session) = &self.session {
+            if let Err(e) = ctx.set_session(session) {
+                warn!("Session is ignored: {e}");
+            }
+        }
+        Downloader {
+            ctx,
+            output_dir: self.output_dir.unwrap_or_else(|| PathBuf::from("./")),
+            flac_allowed: self.flac_allowed,
+            pic_allowed: self.pic_allowed,
+            filename: self.filename,
+            archive: self.archive.map(|archive| Arc::new(Mutex::new(archive))),
+            on_event: self.on_event,
+            cancel: self.cancel.unwrap_or_default(),
+        }
+    }
+}
+
+/// A page of a video or an episode of a bangumi to download.
+struct Page<'a> {
+    // 日志中的名字, 如 BV.. P2、ep..
+    label: String,
+    bvid: &'a str,
+    cid: usize,
+    // 番剧的 ep id, 视频为 None
+    ep_id: Option<u64>,
+    pic: &'a str,
+    filename: String,
+    // 写入的元数据, 之后被 tags 覆盖
+    metadata: Vec<(String, String)>,
+}
+
+/// Downloaded page waiting for transforming.
+struct Pending {
+    index: usize,
+    id: String,
+    format: Format,
+    filename: String,
+    metadata: Vec<(String, String)>,
+    bvid: String,
+    cid: usize,
+    quality: usize,
+    options: ItemOptions,
+}
+
+impl Downloader {
+    pub fn builder() -> DownloaderBuilder {
+        DownloaderBuilder::default()
+    }
+
+    pub fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    pub fn archive(&self) -> Option<&Mutex<Archive>> {
+        self.archive.as_deref()
+    }
+
+    /// Same downloader sending requests with another context, e.g. another account.
+    ///
+    /// The archive, event handler and cancel flag are shared.
+    pub fn with_context(&self, ctx: Context) -> Downloader {
+        Downloader {
+            ctx,
+            output_dir: self.output_dir.clone(),
+            flac_allowed: self.flac_allowed,
+            pic_allowed: self.pic_allowed,
+            filename: self.filename.clone(),
+            archive: self.archive.clone(),
+            on_event: self.on_event.clone(),
+            cancel: Arc::clone(&self.cancel),
+        }
+    }
+
+    fn emit(&self, event: Event) {
+        if let Some(on_event) = &self.on_event {
+            on_event(event);
+        }
+    }
+
+    /// Pick the stream to download, flac is preferred when it is allowed.
+    ///
+    /// Fails with [`Error::NoAudioStream`] when the dash has no stream to pick.
+    pub fn select_stream<'a>(&self, dash: &'a link::Dash) -> Result<(Format, &'a link::Audio)> {
+        self.select_stream_with(dash, &ItemOptions::default())
+    }
+
+    /// Pick the stream by the quality and format of the item.
+    ///
+    /// Audio is transcoded when the format differs from the stream, e.g. flac from 192K.
+    pub fn select_stream_with<'a>(
+        &self,
+        dash: &'a link::Dash,
+        options: &ItemOptions,
+    ) -> Result<(Format, &'a link::Audio)> {
+        let flac = dash
+            .flac
+            .as_ref()
+            .and_then(|flac| flac.audio.as_ref())
+            .filter(|_| options.format != Some(Format::M4a));
+        if let Some(quality) = &options.quality {
+            let matches = |audio: &&link::Audio| {
+                audio.id.to_string() == *quality
+                    || link::quality_name(audio.id).eq_ignore_ascii_case(quality)
+            };
+            if let Some(flac) = flac.filter(matches) {
+                return Ok((Format::Flac, flac));
+            }
+            if let Some(audio) = dash.audio.iter().find(matches) {
+                return Ok((options.format.unwrap_or(Format::M4a), audio));
+            }
+            warn!("Quality {quality} is not available, the best one is used");
+        }
+        if options.format.unwrap_or(Format::Flac) == Format::Flac
+            && (self.flac_allowed || options.format.is_some())
+        {
+            if let Some(flac) = flac {
+                return Ok((Format::Flac, flac));
+            }
+        }
+        let audio = dash.audio.first().ok_or(Error::NoAudioStream)?;
+        Ok((options.format.unwrap_or(Format::M4a), audio))
+    }
+
+    /// Output file name without extension.
+    pub fn output_filename(&self, title: &str) -> String {
+        // If there are more than one ids, you should not to set filename.
+        safe_filename(self.filename.as_deref().unwrap_or(title))
+    }
+
+    /// Output file name of a page, pages are told apart by ` P<page>` when more than one are selected.
+    pub fn page_filename(
+        &self,
+        title: &str,
+        options: &ItemOptions,
+        page: usize,
+        pages: usize,
+    ) -> String {
+        let name = match &options.name {
+            Some(name) => safe_filename(name),
+            None => self.output_filename(title),
+        };
+        match pages > 1 {
+            true => format!("{name} P{page}"),
+            false => name,
+        }
+    }
+
+    pub fn output_path(&self, title: &str, format: Format) -> PathBuf {
+        self.output_dir
+            .join(self.output_filename(title))
+            .with_extension(format.extension())
+    }
+
+    /// Report download progress every 5 percent.
+    fn progress(&self, index: usize) -> Option<Progress> {
+        let on_event = self.on_event.clone()?;
+        let reported = AtomicU64::new(0);
+        Some(Arc::new(move |downloaded, total| {
+            let percent = match total {
+                0 => 100,
+                _ => downloaded * 100 / total,
+            };
+            let last = reported.load(Ordering::SeqCst);
+            if (percent >= last + 5 || downloaded >= total)
+                && reported
+                    .compare_exchange(last, percent, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+            {
+                on_event(Event::DownloadProgress {
+                    index,
+                    downloaded,
+                    total,
+                });
+            }
+        }))
+    }
+
+    /// Download the selected pages or episodes, each is sent to transforming once it is downloaded.
+    ///
+    /// Returns the number of pages sent, 0 when all of them are skipped.
+    async fn download_one(
+        &self,
+        index: usize,
+        item: &Item,
+        tx: &mpsc::UnboundedSender<Pending>,
+    ) -> Result<usize> {
+        match item.id.parse::<BangumiId>() {
+            Ok(id) => self.download_bangumi(index, item, id, tx).await,
+            Err(_) => self.download_video(index, item, tx).await,
+        }
+    }
+
+    async fn download_video(
+        &self,
+        index: usize,
+        item: &Item,
+        tx: &mpsc::UnboundedSender<Pending>,
+    ) -> Result<usize> {
+        let (id, options) = (&item.id, &item.options);
+        // Get audio information.
+        info!("[{index}] Get information about {}", id);
+        let view = view::api(&self.ctx, id).await?;
+        self.emit(Event::MetadataFetched {
+            index,
+            bvid: view.bvid.clone(),
+            title: view.title.clone(),
+            owner: view.owner.name.clone(),
+            duration: view.duration,
+            pages: view.videos,
+        });
+
+        let pages = options.select_pages(&view.bvid, view.pages.len())?;
+
+        let mut sent = 0;
+        for &page in &pages {
+            let filename = self.page_filename(&view.title, options, page, pages.len());
+            let page = Page {
+                label: format!("{} P{page}", view.bvid),
+                bvid: &view.bvid,
+                cid: view.pages[page - 1].cid,
+                ep_id: None,
+                pic: &view.pic,
+                metadata: vec![
+                    ("title".to_string(), filename.clone()),
+                    ("artist".to_string(), view.owner.name.clone()),
+                ],
+                filename,
+            };
+            if self.download_page(index, item, page, tx).await? {
+                sent += 1;
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Episodes of a season are tagged with the season title as album and the episode index as track.
+    async fn download_bangumi(
+        &self,
+        index: usize,
+        item: &Item,
+        id: BangumiId,
+        tx: &mpsc::UnboundedSender<Pending>,
+    ) -> Result<usize> {
+        let options = &item.options;
+        info!("[{index}] Get information about {id}");
+        let season = pgc::season(&self.ctx, id).await?;
+        let episodes = season.select(id, options.pages.as_ref())?;
+        self.emit(Event::MetadataFetched {
+            index,
+            bvid: id.to_string(),
+            title: season.title.clone(),
+            owner: season.up_info.uname.clone(),
+            duration: episodes.iter().map(|(_, e)| e.duration / 1000).sum(),
+            pages: episodes.len(),
+        });
+
+        let mut sent = 0;
+        for &(track, episode) in &episodes {
+            let filename = self.episode_filename(&season, episode, options, track, episodes.len());
+            let mut metadata = vec![
+                ("title".to_string(), episode.name().to_string()),
+                ("album".to_string(), season.title.clone()),
+                ("track".to_string(), track.to_string()),
+            ];
+            if !season.up_info.uname.is_empty() {
+                metadata.push(("artist".to_string(), season.up_info.uname.clone()));
+            }
+            let page = Page {
+                label: format!("ep{}", episode.id),
+                bvid: &episode.bvid,
+                cid: episode.cid,
+                ep_id: Some(episode.id),
+                pic: &episode.cover,
+                filename,
+                metadata,
+            };
+            if self.download_page(index, item, page, tx).await? {
+                sent += 1;
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Output file name of an episode, `<season> <index> <title>` unless a name is given.
+    pub fn episode_filename(
+        &self,
+        season: &pgc::SeasonRsp,
+        episode: &pgc::Episode,
+        options: &ItemOptions,
+        track: usize,
+        tracks: usize,
+    ) -> String {
+        if options.name.is_some() || self.filename.is_some() {
+            return self.page_filename(&season.title, options, track, tracks);
+        }
+        let name = format!("{} {} {}", season.title, episode.title, episode.long_title);
+        safe_filename(name.trim())
+    }
+
+    /// Download a page and send it to transforming, returns false when it is archived.
+    async fn download_page(
+        &self,
+        index: usize,
+        item: &Item,
+        page: Page<'_>,
+        tx: &mpsc::UnboundedSender<Pending>,
+    ) -> Result<bool> {
+        // Skip audio already recorded in the archive.
+        if let Some(archive) = &self.archive {
+            if archive.lock().unwrap().contains(page.bvid, page.cid) {
+                info!(
+                    "[{index}] {} has already been downloaded, skip it",
+                    page.label
+                );
+                self.emit(Event::ItemSkipped {
+                    index,
+                    bvid: page.bvid.to_string(),
+                    reason: "archived".to_string(),
+                });
+                return Ok(false);
+            }
+        }
+
+        // Get audio link.
+        let link = match page.ep_id {
+            Some(ep_id) => pgc::playurl(&self.ctx, ep_id, page.cid, 16 | 256).await?,
+            None => link::api(&self.ctx, page.bvid, page.cid, 16 | 256, None).await?,
+        };
+
+        // Download audio.
+        info!("[{index}] Downloading {}", page.label);
+        let (format, stream) = self.select_stream_with(&link.dash, &item.options)?;
+        let quality = stream.id;
+        self.emit(Event::StreamSelected {
+            index,
+            quality,
+            codecs: stream.codecs.clone(),
+            format: format.extension().to_string(),
+            bandwidth: stream.bandwidth,
+        });
+
+        let path = self.output_dir.to_string_lossy();
+        load_with_progress(
+            &self.ctx.client,
+            &stream.base_url,
+            &page.filename,
+            &path,
+            "m4s",
+            self.progress(index),
+        )
+        .await?;
+
+        if self.pic_allowed {
+            load(&self.ctx.client, page.pic, &page.filename, &path, "jpg").await?;
+        }
+
+        let _ = tx.send(Pending {
+            index,
+            id: item.id.clone(),
+            format,
+            filename: page.filename,
+            metadata: page.metadata,
+            bvid: page.bvid.to_string(),
+            cid: page.cid,
+            quality,
+            options: item.options.clone(),
+        });
+        Ok(true)
+    }
+
+    /// Transform the downloaded file, temporary files are removed in any case.
+    async fn transform_one(&self, context: &Pending) -> Result<Outcome> {
+        info!(
+            "[{}] Starting transform '{}'",
+            context.index, context.filename
+        );
+
+        let path = self.output_dir.join(&context.filename);
+        let source = path.with_extension("m4s");
+        let pic = source.with_extension("jpg");
+        let output = path.with_extension(context.format.extension());
+
+        let result = if !source.exists() {
+            Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Source file not exists.",
+            )))
+        } else if output.exists() {
+            Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "Output file already exists.",
+            )))
+        } else {
+            let mut metadata = context
+                .metadata
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect::<Vec<_>>();
+            metadata.extend(
+                context
+                    .options
+                    .tags
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value.as_str())),
+            );
+            transfer::run(
+                &source.to_string_lossy(),
+                &output.to_string_lossy(),
+                self.pic_allowed.then(|| pic.to_string_lossy()).as_deref(),
+                context.format.extension(),
+                &metadata,
+                context.options.range.as_ref(),
+            )
+            .await
+        };
+
+        let _ = std::fs::remove_file(&source);
+        if self.pic_allowed {
+            let _ = std::fs::remove_file(&pic);
+        }
+        result?;
+
+        info!(
+            "[{}] Finish transforming '{}'",
+            context.index, context.filename
+        );
+
+        let size = std::fs::metadata(&output).map(|m| m.len()).unwrap_or(0);
+        self.emit(Event::TransformDone {
+            index: context.index,
+            output: output.to_string_lossy().to_string(),
+            size,
+        });
+
+        if let Some(archive) = &self.archive {
+            let record = Record::new(&context.bvid, context.cid, context.quality, &output);
+            if let Err(e) = archive.lock().unwrap().add(record) {
+                error!("[{}] Failed to update download archive: {e}", context.index);
+            }
+        }
+
+        Ok(Outcome::Done { output, size })
+    }
+
+    /// Record a live room for the duration or until the live ends, then remux the parts into one file.
+    ///
+    /// It is tagged with the room title and the streamer, and what has been recorded
+    /// is kept when cancelled.
+    pub async fn record(
+        &self,
+        room_id: u64,
+        duration: Option<Duration>,
+        protocol: StreamProtocol,
+        options: &ItemOptions,
+    ) -> Result<Outcome> {
+        info!("Get information about live room {room_id}");
+        let room = live::room_info(&self.ctx, room_id).await?;
+        let (info, uname) = (&room.room_info, &room.anchor_info.base_info.uname);
+        if !room.is_live() {
+            return Err(Error::NotFound(format!("Room {room_id} is not live.")));
+        }
+        self.emit(Event::MetadataFetched {
+            index: 1,
+            bvid: info.room_id.to_string(),
+            title: info.title.clone(),
+            owner: uname.clone(),
+            duration: duration.map_or(0, |duration| duration.as_secs() as usize),
+            pages: 1,
+        });
+
+        let format = options.format.unwrap_or(Format::M4a);
+        let filename = match (&options.name, &self.filename) {
+            (None, None) => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+                let name = format!("{uname} {} {}", info.title, beijing_time(now.as_secs()));
+                safe_filename(name.trim())
+            }
+            _ => self.page_filename(&info.title, options, 1, 1),
+        };
+        let path = self.output_dir.join(&filename);
+        let output = path.with_extension(format.extension());
+        if output.exists() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "Output file already exists.",
+            )));
+        }
+
+        info!(
+            "Recording room {} '{}' of {uname}",
+            info.room_id, info.title
+        );
+        let recording = Recorder::new(self.ctx.client.clone())
+            .duration(duration)
+            .cancel(Arc::clone(&self.cancel))
+            .record(|| live::stream(&self.ctx, info.room_id, protocol), &path)
+            .await?;
+        info!(
+            "Recorded {} bytes in {:?} of {} parts",
+            recording.bytes,
+            recording.elapsed,
+            recording.parts.len()
+        );
+
+        let mut metadata = vec![("title", info.title.as_str())];
+        if !uname.is_empty() {
+            metadata.push(("artist", uname.as_str()));
+        }
+        metadata.extend(
+            options
+                .tags
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str())),
+        );
+        let result = transfer::remux(
+            &recording.parts,
+            &output.to_string_lossy(),
+            format.extension(),
+            &metadata,
+        )
+        .await;
+        for part in &recording.parts {
+            let _ = std::fs::remove_file(part);
+        }
+        result?;
+
+        let size = std::fs::metadata(&output).map(|m| m.len()).unwrap_or(0);
+        self.emit(Event::TransformDone {
+            index: 1,
+            output: output.to_string_lossy().to_string(),
+            size,
+        });
+        Ok(Outcome::Done { output, size })
+    }
+
+    /// Report the failure of an item as soon as it happens.
+    fn finish(&self, index: usize, id: &str, result: Result<Outcome>) -> ItemResult {
+        if let Err(e) = &result {
+            error!("[{index}] {id} failed: {e}");
+            self.emit(Event::ItemFailed {
+                index,
+                id: id.to_string(),
+                kind: e.kind(),
+                message: e.to_string(),
+            });
+        }
+        ItemResult {
+            index,
+            id: id.to_string(),
+            result,
+        }
+    }
+
+    /// Download and transform the ids one by one, transforming runs alongside downloading.
+    ///
+    /// Results are in the order of ids, ids left after cancelling are not included.
+    pub async fn run(&self, ids: &[String]) -> Vec<ItemResult> {
+        let items = ids
+            .iter()
+            .map(|id| Item::from(id.as_str()))
+            .collect::<Vec<_>>();
+        self.run_items(&items).await
+    }
+
+    /// Same as [`Downloader::run`] with options of each item.
+    ///
+    /// An item of several pages has one result for each transformed page.
+    pub async fn run_items(&self, items: &[Item]) -> Vec<ItemResult> {
+        let total_cost = Instant::now();
+        let (tx, mut rx) = mpsc::unbounded_channel::<Pending>();
+
+        let downloads = async {
+            let mut results = Vec::new();
+            for (index, item) in items.iter().enumerate() {
+                let (index, id) = (index + 1, &item.id);
+
+                if self.cancel.load(Ordering::SeqCst) {
+                    warn!(
+                        "Cancelled, skip the remaining {} ids",
+                        items.len() - index + 1
+                    );
+                    break;
+                }
+
+                info!("[{index}] id = {id} starting to work");
+                self.emit(Event::ItemStarted {
+                    index,
+                    id: id.to_string(),
+                });
+
+                match self.download_one(index, item, &tx).await {
+                    Ok(0) => results.push(self.finish(index, id, Ok(Outcome::Skipped))),
+                    Ok(_) => {}
+                    Err(e) => results.push(self.finish(index, id, Err(e))),
+                }
+
+                info!("[{index}] id = {id} finish download");
+            }
+            drop(tx);
+            results
+        };
+
+        let transforms = async {
+            let mut results = Vec::new();
+            while let Some(context) = rx.recv().await {
+                let result = self.transform_one(&context).await;
+                results.push(self.finish(context.index, &context.id, result));
+            }
+            results
+        };
+
+        let (mut results, transformed) = tokio::join!(downloads, transforms);
+        results.extend(transformed);
+        results.sort_by_key(|item| item.index);
+
+        let cost = total_cost.elapsed();
+        let count = |f: fn(&ItemResult) -> bool| results.iter().filter(|item| f(item)).count();
+        let succeeded = count(|item| matches!(item.result, Ok(Outcome::Done { .. })));
+        let skipped = count(|item| matches!(item.result, Ok(Outcome::Skipped)));
+        let failed = count(|item| item.result.is_err());
+        info!("Succeeded: {succeeded}, skipped: {skipped}, failed: {failed}");
+        info!("Total costs: {:?}", cost);
+        self.emit(Event::Summary {
+            total: items.len(),
+            succeeded,
+            skipped,
+            failed,
+            cost_ms: cost.as_millis(),
+        });
+
+        results
+    }
+}
+
+/// `YYYY-MM-DD HH-MM-SS` of the unix time in Beijing time, which bilibili lives follow.
+fn beijing_time(secs: u64) -> String {
+    let secs = secs + 8 * 3600;
+    let (days, rest) = (secs / 86400, secs % 86400);
+    // Civil date from days since 1970-01-01, the algorithm of Howard Hinnant.
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}-{:02}-{:02}",
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+#[test]
+fn test_filename() {
+    assert_eq!(
+        "ABC123 q w e r t y u i o".to_string(),
+        safe_filename("ABC123?q_w*e<r>t|y\"u、i/o")
+    );
+}
+
+#[test]
+fn output_path_test() {
+    let downloader = Downloader::builder().output_dir("music").build();
+    assert_eq!(
+        downloader.output_path("a/b", Format::Flac),
+        PathBuf::from("music").join("a b.flac")
+    );
+
+    let downloader = Downloader::builder().filename("fixed").build();
+    assert_eq!(
+        downloader.output_path("title", Format::M4a),
+        PathBuf::from("./").join("fixed.m4a")
+    );
+}
+
+#[test]
+fn beijing_time_test() {
+    assert_eq!(beijing_time(0), "1970-01-01 08-00-00");
+    assert_eq!(beijing_time(1709222399), "2024-02-29 23-59-59");
+    assert_eq!(beijing_time(951825600), "2000-02-29 20-00-00");
+}
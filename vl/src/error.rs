@@ -0,0 +1,107 @@
+use thiserror::Error;
+
+use crate::event::ErrorKind;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Request failed before getting a response.
+    #[error("network error: {0}")]
+    Network(#[from] reqwest::Error),
+    /// No response or data within the read timeout.
+    #[error("network error: no data received in {0:?}")]
+    Timeout(std::time::Duration),
+    /// Api returns a non-zero code.
+    #[error("api error {code}: {message}")]
+    ApiCode { code: isize, message: String },
+    /// Requests are blocked by risk control, code -412 or -352.
+    #[error("request is blocked by risk control: {0}")]
+    RiskControl(String),
+    /// Api requires an account session.
+    #[error("login required: {0}")]
+    NeedLogin(String),
+    /// Video or resource does not exist or is invisible.
+    #[error("not found: {0}")]
+    NotFound(String),
+    /// Access is denied, such as region limited or charging only.
+    #[error("access denied: {0}")]
+    Forbidden(String),
+    /// Playurl contains no audio stream.
+    #[error("no audio stream available")]
+    NoAudioStream,
+    /// Server ignores the range header of a block download.
+    #[error("server does not support range requests")]
+    RangeUnsupported,
+    /// Ffmpeg fails or can't be run.
+    #[error("transcode failed: {0}")]
+    Transcode(String),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Response body or header is not what we expect.
+    #[error("parse error: {0}")]
+    Parse(String),
+    /// Input given by user is illegal.
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+}
+
+impl Error {
+    /// Translate a non-zero api code.
+    pub fn from_code(code: isize, message: String) -> Self {
+        let detail = format!("{message} ({code})");
+        match code {
+            // 请求错误
+            -400 => Error::InvalidInput(detail),
+            // 账号未登录
+            -101 => Error::NeedLogin(detail),
+            // 访问权限不足 / 地区限制 / 充电专属
+            -403 | -10403 | 87008 => Error::Forbidden(detail),
+            // 啥都木有 / 稿件不可见 / 稿件审核中 / 仅UP主自己可见
+            -404 | 62002 | 62004 | 62012 => Error::NotFound(detail),
+            // 请求被拦截 / 风控校验失败
+            -412 | -352 => Error::RiskControl(detail),
+            _ => Error::ApiCode { code, message },
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Network(_) | Error::Timeout(_) => ErrorKind::Network,
+            Error::ApiCode { .. } => ErrorKind::Api,
+            Error::RiskControl(_) => ErrorKind::RiskControl,
+            Error::NeedLogin(_) => ErrorKind::NeedLogin,
+            Error::NotFound(_) => ErrorKind::NotFound,
+            Error::Forbidden(_) => ErrorKind::Forbidden,
+            Error::NoAudioStream => ErrorKind::NoAudioStream,
+            Error::RangeUnsupported => ErrorKind::RangeUnsupported,
+            Error::Transcode(_) => ErrorKind::Transcode,
+            Error::Io(_) => ErrorKind::Io,
+            Error::Parse(_) => ErrorKind::Parse,
+            Error::InvalidInput(_) => ErrorKind::InvalidInput,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+#[test]
+fn error_test() {
+    assert!(matches!(
+        Error::from_code(-412, "request was banned".to_string()),
+        Error::RiskControl(_)
+    ));
+    assert!(matches!(
+        Error::from_code(62002, "稿件不可见".to_string()),
+        Error::NotFound(_)
+    ));
+    assert!(matches!(
+        Error::from_code(-509, "请求过于频繁".to_string()),
+        Error::ApiCode { code: -509, .. }
+    ));
+    assert_eq!(Error::NoAudioStream.kind(), ErrorKind::NoAudioStream);
+}
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+/// Kind of the error which made an item fail, see [`crate::Error`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Network,
+    Api,
+    RiskControl,
+    NeedLogin,
+    NotFound,
+    Forbidden,
+    NoAudioStream,
+    RangeUnsupported,
+    Transcode,
+    Io,
+    Parse,
+    InvalidInput,
+    Other,
+}
+
+/// Events of the download pipeline, serialized one per line in json output.
+///
+/// Fields are only ever added, so consumers should ignore unknown ones.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    ItemStarted {
+        index: usize,
+        id: String,
+    },
+    MetadataFetched {
+        index: usize,
+        bvid: String,
+        title: String,
+        owner: String,
+        duration: usize,
+        pages: usize,
+    },
+    ItemSkipped {
+        index: usize,
+        bvid: String,
+        reason: String,
+    },
+    StreamSelected {
+        index: usize,
+        quality: usize,
+        codecs: String,
+        format: String,
+        bandwidth: usize,
+    },
+    DownloadProgress {
+        index: usize,
+        downloaded: u64,
+        total: u64,
+    },
+    TransformDone {
+        index: usize,
+        output: String,
+        size: u64,
+    },
+    ItemFailed {
+        index: usize,
+        id: String,
+        kind: ErrorKind,
+        message: String,
+    },
+    Summary {
+        total: usize,
+        succeeded: usize,
+        skipped: usize,
+        failed: usize,
+        cost_ms: u128,
+    },
+}
+
+#[test]
+fn event_test() {
+    let event = Event::ItemStarted {
+        index: 1,
+        id: "BV12g411r7mB".to_string(),
+    };
+    assert_eq!(
+        serde_json::to_string(&event).unwrap(),
+        r#"{"event":"item_started","index":1,"id":"BV12g411r7mB"}"#
+    );
+
+    let event = Event::ItemFailed {
+        index: 1,
+        id: "BV12g411r7mB".to_string(),
+        kind: ErrorKind::RiskControl,
+        message: String::new(),
+    };
+    assert!(serde_json::to_string(&event)
+        .unwrap()
+        .contains(r#""kind":"risk_control""#));
+}
@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::{Error, Result};
+
+const XOR_CODE: u64 = 23442827791579;
+const MASK_CODE: u64 = 2251799813685247;
+const MAX_AID: u64 = 1 << 51;
+const BASE: u64 = 58;
+const TABLE: &[u8; 58] = b"FcwAPNKTMug3GV5Lj7EJnHpWsx4tb8haYeviqBz6rkCy12mUSDQX9RdoZf";
+
+/// Positions swapped after encoding.
+fn swap(bvid: &mut [u8; 12]) {
+    bvid.swap(3, 9);
+    bvid.swap(4, 7);
+}
+
+/// Bvid of an aid, fails for an aid out of `1..2^51`.
+pub fn av2bv(aid: u64) -> Result<String> {
+    VideoId::from_aid(aid).map(|id| encode(id.aid))
+}
+
+/// Bvid of an aid in `1..2^51`.
+fn encode(aid: u64) -> String {
+    let mut bvid = *b"BV1000000000";
+    let mut tmp = (MAX_AID | aid) ^ XOR_CODE;
+    let mut index = bvid.len() - 1;
+    while tmp > 0 {
+        bvid[index] = TABLE[(tmp % BASE) as usize];
+        tmp /= BASE;
+        index -= 1;
+    }
+    swap(&mut bvid);
+    String::from_utf8_lossy(&bvid).to_string()
+}
+
+/// Aid of a bvid, the `BV` prefix is case insensitive.
+pub fn bv2av(bvid: &str) -> Result<u64> {
+    let illegal = || Error::InvalidInput(format!("Bvid '{bvid}' is illegal."));
+    let mut bytes: [u8; 12] = bvid.as_bytes().try_into().map_err(|_| illegal())?;
+    if !bytes[..2].eq_ignore_ascii_case(b"BV") || bytes[2] != b'1' {
+        return Err(illegal());
+    }
+    swap(&mut bytes);
+    let mut tmp = 0;
+    for byte in &bytes[3..] {
+        let value = TABLE.iter().position(|c| c == byte).ok_or_else(illegal)?;
+        tmp = tmp * BASE + value as u64;
+    }
+    // Only the canonical encoding of an aid is legal.
+    match (tmp & MASK_CODE) ^ XOR_CODE {
+        aid if aid > 0 && encode(aid)[2..] == bvid[2..] => Ok(aid),
+        _ => Err(illegal()),
+    }
+}
+
+/// A video given as bvid, `av` prefixed aid or bare aid, which are the same video once parsed.
+///
+/// It is shown and serialized as the canonical bvid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct VideoId {
+    aid: u64,
+}
+
+impl VideoId {
+    pub fn from_aid(aid: u64) -> Result<Self> {
+        match aid > 0 && aid < MAX_AID {
+            true => Ok(VideoId { aid }),
+            false => Err(Error::InvalidInput(format!("Aid {aid} is out of range."))),
+        }
+    }
+
+    pub fn aid(&self) -> u64 {
+        self.aid
+    }
+
+    pub fn bvid(&self) -> String {
+        encode(self.aid)
+    }
+}
+
+impl FromStr for VideoId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let id = s.trim();
+        let illegal = || Error::InvalidInput(format!("Avid/Bvid '{s}' is illegal."));
+        let aid = match id.get(..2).map(str::to_ascii_lowercase).as_deref() {
+            Some("bv") => bv2av(id)?,
+            Some("av") => id[2..].parse::<u64>().map_err(|_| illegal())?,
+            _ => id.parse::<u64>().map_err(|_| illegal())?,
+        };
+        VideoId::from_aid(aid)
+    }
+}
+
+impl Display for VideoId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.bvid())
+    }
+}
+
+impl TryFrom<String> for VideoId {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<VideoId> for String {
+    fn from(id: VideoId) -> Self {
+        id.bvid()
+    }
+}
+
+/// A bangumi episode `ep<id>` or a whole season `ss<id>`, the prefix is case insensitive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BangumiId {
+    Episode(u64),
+    Season(u64),
+}
+
+impl FromStr for BangumiId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let id = s.trim();
+        let illegal = || Error::InvalidInput(format!("Bangumi id '{s}' is illegal."));
+        let number = id
+            .get(2..)
+            .and_then(|n| n.parse::<u64>().ok())
+            .filter(|n| *n > 0)
+            .ok_or_else(illegal)?;
+        match id[..2].to_ascii_lowercase().as_str() {
+            "ep" => Ok(BangumiId::Episode(number)),
+            "ss" => Ok(BangumiId::Season(number)),
+            _ => Err(illegal()),
+        }
+    }
+}
+
+impl Display for BangumiId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BangumiId::Episode(id) => write!(f, "ep{id}"),
+            BangumiId::Season(id) => write!(f, "ss{id}"),
+        }
+    }
+}
+
+#[test]
+fn convert_test() {
+    assert_eq!(av2bv(170001).unwrap(), "BV17x411w7KC");
+    assert_eq!(bv2av("BV17x411w7KC").unwrap(), 170001);
+    assert_eq!(av2bv(111298867365120).unwrap(), "BV1L9Uoa9EUx");
+    for aid in [0, MAX_AID, 1 << 52, u64::MAX] {
+        assert!(av2bv(aid).is_err(), "{aid}");
+    }
+    assert_eq!(bv2av("BV1L9Uoa9EUx").unwrap(), 111298867365120);
+    assert_eq!(bv2av("bv17x411w7KC").unwrap(), 170001);
+    for illegal in [
+        "BV17x411w7K",
+        "BV17x411w7KCC",
+        "BV07x411w7KC",
+        "BV17x411w7K0",
+        "BV1zzzzzzzzz",
+        "BV",
+    ] {
+        assert!(bv2av(illegal).is_err(), "{illegal}");
+    }
+}
+
+#[test]
+fn video_id_test() {
+    let id = "BV17x411w7KC".parse::<VideoId>().unwrap();
+    assert_eq!(id, "av170001".parse().unwrap());
+    assert_eq!(id, "AV170001".parse().unwrap());
+    assert_eq!(id, " 170001 ".parse().unwrap());
+    assert_eq!(id.aid(), 170001);
+    assert_eq!(id.to_string(), "BV17x411w7KC");
+    for illegal in [
+        "",
+        "a",
+        "av",
+        "av0",
+        "avx1",
+        "2251799813685248",
+        "av4503599627370496",
+        "av18446744073709551615",
+        "BV1",
+    ] {
+        let e = illegal.parse::<VideoId>().unwrap_err();
+        assert!(matches!(e, Error::InvalidInput(_)), "{illegal}");
+    }
+}
+
+#[test]
+fn bangumi_id_test() {
+    assert_eq!(
+        "ep508404".parse::<BangumiId>().unwrap(),
+        BangumiId::Episode(508404)
+    );
+    assert_eq!(
+        " SS41410".parse::<BangumiId>().unwrap(),
+        BangumiId::Season(41410)
+    );
+    assert_eq!(BangumiId::Season(41410).to_string(), "ss41410");
+    for illegal in ["ep", "ep0", "md28339", "ss-1", "av170001", "ep1x"] {
+        assert!(illegal.parse::<BangumiId>().is_err(), "{illegal}");
+    }
+}
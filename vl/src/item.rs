@@ -0,0 +1,281 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::downloader::Format;
+use crate::{Error, Result};
+
+/// Pages of a video, e.g. `1`, `1,3`, `2-4`, `3-` or `all`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Pages(Vec<(usize, Option<usize>)>);
+
+impl Pages {
+    /// Every page of the video.
+    pub fn all() -> Self {
+        Pages(vec![(1, None)])
+    }
+
+    /// Only the given page.
+    pub fn one(page: usize) -> Self {
+        Pages(vec![(page, Some(page))])
+    }
+
+    /// Page numbers from 1 selected out of `count` pages, in the given order without duplicates.
+    pub fn select(&self, count: usize) -> Vec<usize> {
+        let mut pages = Vec::new();
+        for &(start, end) in &self.0 {
+            for page in start..=end.unwrap_or(count).min(count) {
+                if !pages.contains(&page) {
+                    pages.push(page);
+                }
+            }
+        }
+        pages
+    }
+}
+
+impl FromStr for Pages {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let illegal = || {
+            Error::InvalidInput(format!(
+                "Pages '{s}' is illegal, expect all or numbers like 1,3,5-7,9-."
+            ))
+        };
+        let page = |page: &str| match page.trim().parse::<usize>() {
+            Ok(page) if page > 0 => Ok(page),
+            _ => Err(illegal()),
+        };
+        if matches!(s.trim(), "all" | "*") {
+            return Ok(Pages::all());
+        }
+        let mut ranges = Vec::new();
+        for part in s.split(',') {
+            let range = match part.split_once('-') {
+                None => (page(part)?, Some(page(part)?)),
+                Some((start, end)) if end.trim().is_empty() => (page(start)?, None),
+                Some((start, end)) => (page(start)?, Some(page(end)?)),
+            };
+            if matches!(range, (start, Some(end)) if end < start) {
+                return Err(illegal());
+            }
+            ranges.push(range);
+        }
+        Ok(Pages(ranges))
+    }
+}
+
+impl Display for Pages {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranges = self
+            .0
+            .iter()
+            .map(|range| match range {
+                (start, Some(end)) if start == end => start.to_string(),
+                (start, Some(end)) => format!("{start}-{end}"),
+                (start, None) => format!("{start}-"),
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", ranges.join(","))
+    }
+}
+
+impl TryFrom<String> for Pages {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<Pages> for String {
+    fn from(pages: Pages) -> Self {
+        pages.to_string()
+    }
+}
+
+/// Part of the audio to keep in seconds, e.g. `1:30-2:45`, `90-` or `-1:00.5`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeRange {
+    pub start: f64,
+    pub end: Option<f64>,
+}
+
+/// Seconds of `[[hh:]mm:]ss[.fff]`.
+pub fn parse_time(time: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    let parts = time.trim().split(':').collect::<Vec<_>>();
+    if parts.len() > 3 {
+        return None;
+    }
+    for part in parts {
+        let value = part.parse::<f64>().ok().filter(|v| *v >= 0.0)?;
+        seconds = seconds * 60.0 + value;
+    }
+    Some(seconds)
+}
+
+impl FromStr for TimeRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let illegal = || {
+            Error::InvalidInput(format!(
+                "Time range '{s}' is illegal, expect [start]-[end] like 1:30-2:45 or 90-."
+            ))
+        };
+        let (start, end) = s.split_once('-').ok_or_else(illegal)?;
+        let start = match start.trim() {
+            "" => 0.0,
+            start => parse_time(start).ok_or_else(illegal)?,
+        };
+        let end = match end.trim() {
+            "" => None,
+            end => Some(
+                parse_time(end)
+                    .filter(|end| *end > start)
+                    .ok_or_else(illegal)?,
+            ),
+        };
+        Ok(TimeRange { start, end })
+    }
+}
+
+impl Display for TimeRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-", self.start)?;
+        match self.end {
+            Some(end) => write!(f, "{end}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl TryFrom<String> for TimeRange {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<TimeRange> for String {
+    fn from(range: TimeRange) -> Self {
+        range.to_string()
+    }
+}
+
+/// Options of one item, overriding the ones of [`crate::downloader::Downloader`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ItemOptions {
+    // 输出文件名, 不含扩展名
+    pub name: Option<String>,
+    // 输出格式, m4a 或 flac
+    pub format: Option<Format>,
+    // 音质代码 (30280) 或名称 (192K)
+    pub quality: Option<String>,
+    // 下载的分P, 默认只有 P1; 番剧季度中选中的集, 默认全部
+    pub pages: Option<Pages>,
+    // 截取的时间段
+    pub range: Option<TimeRange>,
+    // 额外写入的元数据标签
+    pub tags: BTreeMap<String, String>,
+}
+
+impl ItemOptions {
+    /// Pages of a video with `count` pages to download, only P1 when no pages are given.
+    ///
+    /// Fails when the given pages select nothing.
+    pub fn select_pages(&self, bvid: &str, count: usize) -> Result<Vec<usize>> {
+        let pages = match &self.pages {
+            Some(pages) => pages.select(count),
+            None => vec![1],
+        };
+        if pages.is_empty() {
+            return Err(Error::InvalidInput(format!(
+                "Pages {} are not in the {count} pages of {bvid}",
+                self.pages
+                    .as_ref()
+                    .map(Pages::to_string)
+                    .unwrap_or_default(),
+            )));
+        }
+        Ok(pages)
+    }
+}
+
+/// An id to download with its own options.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Item {
+    pub id: String,
+    pub options: ItemOptions,
+}
+
+impl Item {
+    pub fn new<S: Into<String>>(id: S, options: ItemOptions) -> Self {
+        Item {
+            id: id.into(),
+            options,
+        }
+    }
+}
+
+impl From<&str> for Item {
+    fn from(id: &str) -> Self {
+        Item::new(id, ItemOptions::default())
+    }
+}
+
+impl From<String> for Item {
+    fn from(id: String) -> Self {
+        Item::new(id, ItemOptions::default())
+    }
+}
+
+#[test]
+fn pages_test() {
+    let pages = "3,1-2,5-".parse::<Pages>().unwrap();
+    assert_eq!(pages.select(6), vec![3, 1, 2, 5, 6]);
+    assert_eq!(pages.select(2), vec![1, 2]);
+    assert_eq!(pages.to_string(), "3,1-2,5-");
+    assert_eq!("all".parse::<Pages>().unwrap().select(3), vec![1, 2, 3]);
+    for illegal in ["0", "3-1", "a", "1,,2", ""] {
+        assert!(illegal.parse::<Pages>().is_err(), "{illegal}");
+    }
+
+    let options = |pages: Option<&str>| ItemOptions {
+        pages: pages.map(|pages| pages.parse().unwrap()),
+        ..Default::default()
+    };
+    assert_eq!(options(None).select_pages("BV1", 2).unwrap(), vec![1]);
+    assert_eq!(
+        options(Some("2-")).select_pages("BV1", 3).unwrap(),
+        vec![2, 3]
+    );
+    let e = options(Some("9")).select_pages("BV1", 2).unwrap_err();
+    assert!(matches!(e, Error::InvalidInput(_)));
+}
+
+#[test]
+fn time_range_test() {
+    let range = "1:30-1:02:03.5".parse::<TimeRange>().unwrap();
+    assert_eq!(range.start, 90.0);
+    assert_eq!(range.end, Some(3723.5));
+    assert_eq!(range.to_string(), "90-3723.5");
+    assert_eq!(
+        "-45".parse::<TimeRange>().unwrap(),
+        TimeRange {
+            start: 0.0,
+            end: Some(45.0)
+        }
+    );
+    assert_eq!("20-".parse::<TimeRange>().unwrap().end, None);
+    for illegal in ["90", "2:00-1:00", "a-b", "1:2:3:4-"] {
+        assert!(illegal.parse::<TimeRange>().is_err(), "{illegal}");
+    }
+}
@@ -1,8 +1,19 @@
+pub mod archive;
 pub mod catcher;
 /// TODO:
 /// - auth 接口
 /// - ffmpeg 命令优化
 /// - config 配置
 mod common;
+pub mod cookies;
+pub mod downloader;
+mod error;
+pub mod event;
+pub mod id;
+pub mod item;
 pub mod loader;
+pub mod resolve;
 pub mod transfer;
+
+pub use common::{Client, ClientConfig, Context, HttpVersion, ProxyConfig, TlsBackend};
+pub use error::{Error, Result};
@@ -1,29 +1,58 @@
 use std::{io::SeekFrom, time::Instant};
 
-use anyhow::Result;
 use futures::future::join_all;
 use futures::StreamExt;
 use log::debug;
 use reqwest::header;
 use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::IntoUrl;
+use reqwest::{IntoUrl, Method, StatusCode};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::fs::File;
 use tokio::io::AsyncSeekExt;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
 
-use crate::common::CLIENT;
+use crate::common::Client;
+use crate::{Error, Result};
 
-async fn judge(url: &str) -> Result<(u64, bool)> {
-    let headers = CLIENT.head(url).await?;
+mod record;
 
-    let content_length = headers
-        .get(header::CONTENT_LENGTH)
-        .unwrap()
-        .to_str()?
-        .parse::<u64>()?;
+pub use record::{LiveStream, Recorder, Recording, StreamProtocol};
+
+/// Progress callback with the downloaded bytes and the total bytes.
+pub type Progress = Arc<dyn Fn(u64, u64) + Send + Sync>;
+
+/// Counts downloaded bytes of all blocks and reports them.
+struct Tracker {
+    downloaded: AtomicU64,
+    total: u64,
+    progress: Option<Progress>,
+}
+
+impl Tracker {
+    fn advance(&self, len: u64) {
+        let downloaded = self.downloaded.fetch_add(len, Ordering::SeqCst) + len;
+        if let Some(progress) = &self.progress {
+            progress(downloaded, self.total);
+        }
+    }
+}
+
+/// Get the content length and whether range requests are supported by a HEAD request.
+pub async fn judge(client: &Client, url: &str) -> Result<(u64, bool)> {
+    let headers = client.head(url).await?;
+
+    // Without a content length we can only download it in one stream.
+    let content_length = match headers.get(header::CONTENT_LENGTH) {
+        None => return Ok((0, false)),
+        Some(v) => v
+            .to_str()
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .ok_or_else(|| Error::Parse("Content-Length is illegal.".to_string()))?,
+    };
 
     let can_muti = match headers.get(header::ACCEPT_RANGES) {
         None => false,
@@ -35,42 +64,66 @@ async fn judge(url: &str) -> Result<(u64, bool)> {
     Ok((content_length, can_muti))
 }
 
-async fn fetch_one(url: &str, mut file: File) -> Result<()> {
-    let mut stream = CLIENT.get_byte_stream(url, None).await?;
+async fn fetch_one(
+    client: &Client,
+    url: &str,
+    mut file: File,
+    tracker: Arc<Tracker>,
+) -> Result<()> {
+    let mut stream = client.get_byte_stream(url, None).await?;
     let mut start: u64 = 0;
-    while let Some(chunk) = stream.next().await {
+    while let Some(chunk) = client.timed(stream.next()).await? {
         let mut chunk = chunk?;
         file.seek(SeekFrom::Start(start)).await?;
         start += chunk.len() as u64;
+        tracker.advance(chunk.len() as u64);
         file.write_all_buf(&mut chunk).await?;
     }
+    // Writes of tokio files may still be in flight until flushed.
+    file.flush().await?;
     Ok(())
 }
 
 async fn muti_download<U: IntoUrl>(
+    client: Client,
     url: U,
     (mut start, end): (u64, u64),
     file: Arc<Mutex<File>>,
+    tracker: Arc<Tracker>,
 ) -> Result<()> {
     let mut headers = HeaderMap::new();
     let range = match end {
         u64::MAX => format!("bytes={start}-"),
         _ => format!("bytes={start}-{end}"),
     };
-    headers.insert(header::RANGE, HeaderValue::from_str(&range)?);
-    let mut stream = CLIENT.get_byte_stream(url, Some(headers)).await?;
+    headers.insert(header::RANGE, HeaderValue::from_str(&range).unwrap());
+    let response = client
+        .request(Method::GET, url, &(), Some(headers))
+        .await?
+        .error_for_status()?;
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(Error::RangeUnsupported);
+    }
+    let mut stream = response.bytes_stream();
 
-    while let Some(chunk) = stream.next().await {
+    while let Some(chunk) = client.timed(stream.next()).await? {
         let mut chunk = chunk?;
         let mut file = file.lock().await;
         file.seek(SeekFrom::Start(start)).await?;
         start += chunk.len() as u64;
+        tracker.advance(chunk.len() as u64);
         file.write_all_buf(&mut chunk).await?;
     }
     Ok(())
 }
 
-async fn fetch_muti<U: IntoUrl>(url: U, content_length: u64, file: File) -> Result<()> {
+async fn fetch_muti<U: IntoUrl>(
+    client: &Client,
+    url: U,
+    content_length: u64,
+    file: File,
+    tracker: Arc<Tracker>,
+) -> Result<()> {
     let block_num = num_cpus::get() as u64;
     let url = url.into_url()?;
 
@@ -81,40 +134,68 @@ async fn fetch_muti<U: IntoUrl>(url: U, content_length: u64, file: File) -> Resu
         for i in 0..(block_num - 1) {
             let file = Arc::clone(&file);
             handles.push(tokio::spawn(muti_download(
+                client.clone(),
                 url.clone(),
                 (block_size * i, block_size * (i + 1) - 1),
                 file,
+                Arc::clone(&tracker),
             )));
         }
     }
 
-    let file = Arc::clone(&file);
     handles.push(tokio::spawn(muti_download(
+        client.clone(),
         url.clone(),
         (block_size * (block_num - 1), u64::MAX),
-        file,
+        Arc::clone(&file),
+        tracker,
     )));
 
-    let res = join_all(handles).await;
-    let error = res.into_iter().flatten().any(|r| r.is_err());
-
-    if error {
-        return Err(anyhow::Error::msg("Download file failed."));
+    for res in join_all(handles).await {
+        match res {
+            Ok(res) => res?,
+            Err(e) => return Err(Error::Io(std::io::Error::other(e))),
+        }
     }
 
+    file.lock().await.flush().await?;
     Ok(())
 }
 
-pub async fn load(url: &str, filename: &str, path: &str, extension: &str) -> Result<()> {
-    let (content_length, can_muti) = judge(url).await?;
+pub async fn load(
+    client: &Client,
+    url: &str,
+    filename: &str,
+    path: &str,
+    extension: &str,
+) -> Result<()> {
+    load_with_progress(client, url, filename, path, extension, None).await
+}
+
+/// Same as [`load`], reporting progress after every chunk written.
+pub async fn load_with_progress(
+    client: &Client,
+    url: &str,
+    filename: &str,
+    path: &str,
+    extension: &str,
+    progress: Option<Progress>,
+) -> Result<()> {
+    let (content_length, can_muti) = judge(client, url).await?;
+    let tracker = Arc::new(Tracker {
+        downloaded: AtomicU64::new(0),
+        total: content_length,
+        progress,
+    });
     let start = Instant::now();
 
     let filename = PathBuf::from(path).join(filename).with_extension(extension);
 
     let file = File::create(filename).await?;
-    match can_muti {
-        true => fetch_muti(url, content_length, file).await?,
-        false => fetch_one(url, file).await?,
+    // Blocks would be empty when there are fewer bytes than blocks, e.g. an empty body.
+    match can_muti && content_length >= num_cpus::get() as u64 {
+        true => fetch_muti(client, url, content_length, file, tracker).await?,
+        false => fetch_one(client, url, file, tracker).await?,
     };
 
     debug!("Download time costs: {:?}", start.elapsed());
@@ -0,0 +1,462 @@
+use futures::{Future, StreamExt};
+use log::{info, warn};
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+
+use crate::common::Client;
+use crate::{Error, Result};
+
+/// Protocol of a live stream.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamProtocol {
+    /// One endless http-flv response.
+    #[default]
+    Flv,
+    /// A m3u8 playlist polled for new segments.
+    Hls,
+}
+
+impl FromStr for StreamProtocol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "flv" => Ok(StreamProtocol::Flv),
+            "hls" => Ok(StreamProtocol::Hls),
+            _ => Err(Error::InvalidInput(format!(
+                "Protocol '{s}' is illegal, expect flv or hls."
+            ))),
+        }
+    }
+}
+
+/// Url of a live stream, got again on every reconnection as the urls expire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveStream {
+    pub url: String,
+    pub protocol: StreamProtocol,
+    // 分片扩展名, flv、ts 或 m4s
+    pub extension: String,
+}
+
+/// What has been recorded, one part for each connection.
+#[derive(Debug, Default)]
+pub struct Recording {
+    pub parts: Vec<PathBuf>,
+    pub bytes: u64,
+    pub elapsed: Duration,
+    // 直播是否已经结束, 否则是到达时长或被取消
+    pub ended: bool,
+}
+
+/// Why a connection stops.
+enum Stop {
+    Deadline,
+    Cancelled,
+    /// The playlist has `#EXT-X-ENDLIST`.
+    Ended,
+    /// The response ends without telling whether the live ends.
+    Dropped,
+}
+
+/// Records a live stream for a duration or until the live ends, reconnecting on drops.
+///
+/// The stream source fails with [`Error::NotFound`] once the live ends.
+pub struct Recorder {
+    client: Client,
+    duration: Option<Duration>,
+    max_retries: usize,
+    retry_delay: Duration,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Recorder {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            duration: None,
+            max_retries: 5,
+            retry_delay: Duration::from_secs(3),
+            cancel: Default::default(),
+        }
+    }
+
+    /// Stop after the duration [default: until the live ends]
+    pub fn duration(mut self, duration: Option<Duration>) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Give up after the number of reconnections in a row which get nothing [default: 5]
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Wait before reconnecting [default: 3s]
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Stop recording once the flag is set, what has been recorded is kept.
+    pub fn cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+
+    /// Run the future until the deadline, `None` when the deadline comes first.
+    async fn until<F: Future>(deadline: Option<Instant>, future: F) -> Option<F::Output> {
+        match deadline {
+            None => Some(future.await),
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), future).await.ok(),
+        }
+    }
+
+    /// Record into parts named `<path>.part<n>.<extension>`, which are left to the caller.
+    ///
+    /// Fails only when nothing is recorded, a recording cut by errors is returned as it is.
+    pub async fn record<F, Fut>(&self, mut source: F, path: &Path) -> Result<Recording>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<LiveStream>>,
+    {
+        let start = Instant::now();
+        let deadline = self.duration.map(|duration| start + duration);
+        let mut recording = Recording::default();
+        let mut failures = 0;
+
+        let last_error = loop {
+            if self.cancelled() || deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break None;
+            }
+            let result = match source().await {
+                Err(Error::NotFound(message)) => {
+                    info!("Live is not on: {message}");
+                    recording.ended = true;
+                    break Some(Error::NotFound(message));
+                }
+                Err(e) => Err(e),
+                Ok(stream) => {
+                    let part = PathBuf::from(format!(
+                        "{}.part{}.{}",
+                        path.display(),
+                        recording.parts.len() + 1,
+                        stream.extension
+                    ));
+                    info!("Recording {} into {}", stream.url, part.display());
+                    let mut written = 0;
+                    let result = self
+                        .record_part(&stream, &part, deadline, &mut written)
+                        .await;
+                    match written > 0 {
+                        true => {
+                            recording.parts.push(part);
+                            recording.bytes += written;
+                            failures = 0;
+                            result
+                        }
+                        false => {
+                            let _ = std::fs::remove_file(&part);
+                            // A stream dropped before any data counts as a failure.
+                            match result {
+                                Ok(Stop::Dropped) => Err(Error::NoAudioStream),
+                                result => result,
+                            }
+                        }
+                    }
+                }
+            };
+            match result {
+                Ok(Stop::Deadline) | Ok(Stop::Cancelled) => break None,
+                Ok(Stop::Ended) => {
+                    recording.ended = true;
+                    break None;
+                }
+                Ok(Stop::Dropped) => {
+                    warn!("Live stream is dropped, reconnecting");
+                }
+                Err(e) => {
+                    warn!("Live stream fails, reconnecting: {e}");
+                    failures += 1;
+                    if failures > self.max_retries {
+                        break Some(e);
+                    }
+                }
+            }
+            if let Some(deadline) = deadline {
+                tokio::time::sleep_until(deadline.min(Instant::now() + self.retry_delay).into())
+                    .await;
+            } else {
+                tokio::time::sleep(self.retry_delay).await;
+            }
+        };
+
+        recording.elapsed = start.elapsed();
+        match (recording.parts.is_empty(), last_error) {
+            (true, Some(e)) => Err(e),
+            (true, None) => Err(Error::NotFound("Nothing is recorded.".to_string())),
+            (false, Some(e)) if !recording.ended => {
+                warn!("Recording stops after {failures} failed reconnections: {e}");
+                Ok(recording)
+            }
+            _ => Ok(recording),
+        }
+    }
+
+    async fn record_part(
+        &self,
+        stream: &LiveStream,
+        part: &Path,
+        deadline: Option<Instant>,
+        written: &mut u64,
+    ) -> Result<Stop> {
+        let mut file = File::create(part).await?;
+        let result = match stream.protocol {
+            StreamProtocol::Flv => {
+                self.record_flv(&stream.url, &mut file, deadline, written)
+                    .await
+            }
+            StreamProtocol::Hls => {
+                self.record_hls(&stream.url, &mut file, deadline, written)
+                    .await
+            }
+        };
+        file.flush().await?;
+        result
+    }
+
+    async fn record_flv(
+        &self,
+        url: &str,
+        file: &mut File,
+        deadline: Option<Instant>,
+        written: &mut u64,
+    ) -> Result<Stop> {
+        let mut stream = self.client.get_byte_stream(url, None).await?;
+        loop {
+            if self.cancelled() {
+                return Ok(Stop::Cancelled);
+            }
+            let chunk = match Self::until(deadline, self.client.timed(stream.next())).await {
+                None => return Ok(Stop::Deadline),
+                Some(chunk) => chunk?,
+            };
+            match chunk {
+                None => return Ok(Stop::Dropped),
+                Some(chunk) => {
+                    let chunk = chunk?;
+                    file.write_all(&chunk).await?;
+                    *written += chunk.len() as u64;
+                }
+            }
+        }
+    }
+
+    async fn get_bytes(&self, url: &Url) -> Result<bytes::Bytes> {
+        let response = self
+            .client
+            .request(Method::GET, url.clone(), &(), None)
+            .await?
+            .error_for_status()?;
+        Ok(self.client.timed(response.bytes()).await??)
+    }
+
+    async fn record_hls(
+        &self,
+        url: &str,
+        file: &mut File,
+        deadline: Option<Instant>,
+        written: &mut u64,
+    ) -> Result<Stop> {
+        let mut url = Url::parse(url)
+            .map_err(|_| Error::InvalidInput(format!("Stream url '{url}' is illegal.")))?;
+        let mut next_sequence = None;
+        let mut map_written = false;
+        loop {
+            if self.cancelled() {
+                return Ok(Stop::Cancelled);
+            }
+            let playlist = match Self::until(deadline, self.get_bytes(&url)).await {
+                None => return Ok(Stop::Deadline),
+                Some(bytes) => Playlist::parse(&String::from_utf8_lossy(&bytes?))?,
+            };
+            if let Some(variant) = &playlist.variant {
+                url = join(&url, variant)?;
+                continue;
+            }
+            // Sequence numbers going back means the stream is restarted.
+            let end = playlist.media_sequence + playlist.segments.len() as u64;
+            if next_sequence.is_some_and(|next| end < next) {
+                return Ok(Stop::Dropped);
+            }
+
+            let skipped =
+                next_sequence.map_or(0, |next| next.saturating_sub(playlist.media_sequence));
+            let init = playlist.map.iter().filter(|_| !map_written);
+            for uri in init.chain(playlist.segments.iter().skip(skipped as usize)) {
+                if self.cancelled() {
+                    return Ok(Stop::Cancelled);
+                }
+                let bytes = match Self::until(deadline, self.get_bytes(&join(&url, uri)?)).await {
+                    None => return Ok(Stop::Deadline),
+                    Some(bytes) => bytes?,
+                };
+                file.write_all(&bytes).await?;
+                *written += bytes.len() as u64;
+            }
+            map_written |= playlist.map.is_some();
+            next_sequence = next_sequence.max(Some(end));
+
+            if playlist.ended {
+                return Ok(Stop::Ended);
+            }
+            let wait = Duration::from_secs_f64(playlist.target_duration / 2.0);
+            if Self::until(deadline, tokio::time::sleep(wait))
+                .await
+                .is_none()
+            {
+                return Ok(Stop::Deadline);
+            }
+        }
+    }
+}
+
+fn join(base: &Url, uri: &str) -> Result<Url> {
+    base.join(uri)
+        .map_err(|_| Error::Parse(format!("Playlist uri '{uri}' is illegal.")))
+}
+
+/// Value of an attribute in a list like `URI="init.mp4?a=1,b=2",BYTERANGE="720@0"`,
+/// commas in quoted strings don't separate attributes.
+fn attribute<'a>(list: &'a str, name: &str) -> Option<&'a str> {
+    let mut quoted = false;
+    list.split(|c| {
+        quoted ^= c == '"';
+        c == ',' && !quoted
+    })
+    .find_map(|attr| attr.trim().strip_prefix(name)?.strip_prefix('='))
+    .map(|value| value.trim_matches('"'))
+}
+
+/// Seconds of the longest target duration, playlists are polled every half of it.
+const MAX_TARGET_DURATION: f64 = 60.0;
+
+/// The parts of a m3u8 playlist needed by recording.
+#[derive(Debug, Default, PartialEq)]
+struct Playlist {
+    media_sequence: u64,
+    target_duration: f64,
+    // fmp4 的初始化分片
+    map: Option<String>,
+    segments: Vec<String>,
+    // 主播放列表中的第一个子播放列表
+    variant: Option<String>,
+    ended: bool,
+}
+
+impl Playlist {
+    fn parse(text: &str) -> Result<Playlist> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next() != Some("#EXTM3U") {
+            return Err(Error::Parse("Playlist is not a m3u8 file.".to_string()));
+        }
+        let mut playlist = Playlist {
+            target_duration: 1.0,
+            ..Default::default()
+        };
+        let mut variant = false;
+        for line in lines {
+            match line.split_once(':') {
+                Some(("#EXT-X-MEDIA-SEQUENCE", value)) => {
+                    playlist.media_sequence = value.parse().unwrap_or_default()
+                }
+                Some(("#EXT-X-TARGETDURATION", value)) => {
+                    // Waits are half of it, a negative, NaN or infinite one can't be slept.
+                    playlist.target_duration = value
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|duration: &f64| duration.is_finite() && *duration > 0.0)
+                        .map_or(1.0, |duration| duration.min(MAX_TARGET_DURATION))
+                }
+                Some(("#EXT-X-MAP", value)) => {
+                    playlist.map = attribute(value, "URI").map(str::to_string)
+                }
+                Some(("#EXT-X-STREAM-INF", _)) => variant = true,
+                _ if line == "#EXT-X-ENDLIST" => playlist.ended = true,
+                _ if line.starts_with('#') => {}
+                _ if variant => {
+                    playlist.variant.get_or_insert_with(|| line.to_string());
+                }
+                _ => playlist.segments.push(line.to_string()),
+            }
+        }
+        Ok(playlist)
+    }
+}
+
+#[test]
+fn playlist_test() {
+    let playlist = Playlist::parse(
+        "#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-MEDIA-SEQUENCE:1024\n#EXT-X-TARGETDURATION:2\n\
+         #EXT-X-MAP:URI=\"h1024.m4s\"\n#EXTINF:1.00,\n1025.m4s\n#EXTINF:1.00,\n1026.m4s\n",
+    )
+    .unwrap();
+    assert_eq!(
+        playlist,
+        Playlist {
+            media_sequence: 1024,
+            target_duration: 2.0,
+            map: Some("h1024.m4s".to_string()),
+            segments: vec!["1025.m4s".to_string(), "1026.m4s".to_string()],
+            variant: None,
+            ended: false,
+        }
+    );
+
+    let playlist = Playlist::parse(
+        "#EXTM3U\r\n#EXT-X-STREAM-INF:BANDWIDTH=1280000\r\nhigh/index.m3u8\r\n\
+         #EXT-X-STREAM-INF:BANDWIDTH=640000\r\nlow/index.m3u8\r\n",
+    )
+    .unwrap();
+    assert_eq!(playlist.variant.as_deref(), Some("high/index.m3u8"));
+    assert!(playlist.segments.is_empty());
+
+    let playlist = Playlist::parse(
+        "#EXTM3U\n#EXT-X-MAP:BYTERANGE=\"720@0\",URI=\"init.mp4?a=1,b=2\"\n#EXTINF:1,\n0.m4s\n",
+    )
+    .unwrap();
+    assert_eq!(playlist.map.as_deref(), Some("init.mp4?a=1,b=2"));
+    assert_eq!(
+        attribute("BANDWIDTH=1,CODECS=\"mp4a.40.2,avc1\"", "CODECS"),
+        Some("mp4a.40.2,avc1")
+    );
+    assert_eq!(attribute("XURI=\"a\"", "URI"), None);
+
+    let playlist = Playlist::parse("#EXTM3U\n#EXTINF:1,\n0.ts\n#EXT-X-ENDLIST\n").unwrap();
+    assert!(playlist.ended);
+    assert!(Playlist::parse("<html></html>").is_err());
+
+    let playlist = Playlist::parse("#EXTM3U\n#EXT-X-TARGETDURATION:1e300\n0.ts\n").unwrap();
+    assert_eq!(playlist.target_duration, MAX_TARGET_DURATION);
+    for duration in ["-2", "0", "NaN", "inf", "1e400", "x"] {
+        let playlist = Playlist::parse(&format!(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:{duration}\n0.ts\n"
+        ))
+        .unwrap();
+        assert_eq!(playlist.target_duration, 1.0, "{duration}");
+    }
+}
@@ -0,0 +1,248 @@
+use futures::stream::{self, StreamExt};
+use reqwest::Url;
+use std::fmt::{self, Display};
+
+use crate::id::{BangumiId, VideoId};
+use crate::{Client, Error, Result};
+
+/// Hosts of short links, which redirect to full links.
+pub static SHORT_HOSTS: &[&str] = &["b23.tv", "bili2233.cn", "bili22.cn", "bili33.cn"];
+
+/// Redirections followed at most for a short link.
+const MAX_HOPS: usize = 5;
+
+/// Inputs resolved at the same time by [`resolve_all`].
+const CONCURRENCY: usize = 8;
+
+/// What an id or a link points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// A video, with the page given by `p=` of the link.
+    Video { id: VideoId, page: Option<usize> },
+    /// A bangumi episode or season.
+    Bangumi(BangumiId),
+    /// A live room, the id can be the short one.
+    Live(u64),
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Video { id, page: None } => write!(f, "{id}"),
+            Target::Video {
+                id,
+                page: Some(page),
+            } => write!(f, "{id}?p={page}"),
+            Target::Bangumi(id) => write!(f, "{id}"),
+            Target::Live(room_id) => write!(f, "live/{room_id}"),
+        }
+    }
+}
+
+/// Target of a full link without any request, `None` for short links and unknown pages.
+///
+/// Known pages are `/video/`, `/festival/` and `/list/` with a `bvid` param, and
+/// `/bangumi/play/ep|ss` on `bilibili.com`, `www.bilibili.com` or `m.bilibili.com`,
+/// and rooms on `live.bilibili.com`.
+pub fn parse_link(url: &Url) -> Option<Target> {
+    let host = url.host_str()?;
+    if host == "live.bilibili.com" {
+        let room_id = match url.path_segments()?.collect::<Vec<_>>().as_slice() {
+            ["h5" | "blanc", room_id, ..] | [room_id, ..] => room_id.parse().ok(),
+            _ => None,
+        };
+        return room_id.filter(|room_id| *room_id > 0).map(Target::Live);
+    }
+    if !matches!(host, "bilibili.com" | "www.bilibili.com" | "m.bilibili.com") {
+        return None;
+    }
+    let query = |key: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+    };
+    let page = query("p")
+        .and_then(|p| p.parse::<usize>().ok())
+        .filter(|p| *p > 0);
+    let segments = url
+        .path_segments()?
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+    let id = match segments.as_slice() {
+        ["video", id, ..] => id.parse().ok()?,
+        ["festival", ..] | ["list", ..] => ["bvid", "aid", "oid"]
+            .iter()
+            .find_map(|key| query(key)?.parse().ok())?,
+        ["bangumi", "play", id, ..] => return id.parse().ok().map(Target::Bangumi),
+        _ => return None,
+    };
+    Some(Target::Video { id, page })
+}
+
+/// An http(s) url of the input, the scheme can be omitted like `b23.tv/xxx`.
+fn to_url(input: &str) -> Option<Url> {
+    let url = match input.contains("://") {
+        true => Url::parse(input),
+        false => Url::parse(&format!("https://{input}")),
+    };
+    url.ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Resolve an aid/bvid, `ep`/`ss` id or link, short links are followed hop by hop until a known link.
+pub async fn resolve(client: &Client, input: &str) -> Result<Target> {
+    resolve_with(client, input, SHORT_HOSTS).await
+}
+
+/// Same as [`resolve`] with other short link hosts.
+///
+/// The client had better follow no redirection, so that every hop is checked
+/// without visiting the final page.
+pub async fn resolve_with(client: &Client, input: &str, short_hosts: &[&str]) -> Result<Target> {
+    let input = input.trim();
+    if let Ok(id) = input.parse::<VideoId>() {
+        return Ok(Target::Video { id, page: None });
+    }
+    if let Ok(id) = input.parse::<BangumiId>() {
+        return Ok(Target::Bangumi(id));
+    }
+    let mut url = to_url(input).ok_or_else(|| {
+        Error::InvalidInput(format!("Input '{input}' is not a video id or link."))
+    })?;
+    for hop in 0.. {
+        if let Some(target) = parse_link(&url) {
+            return Ok(target);
+        }
+        let short = url
+            .host_str()
+            .is_some_and(|host| short_hosts.contains(&host));
+        if !short || hop == MAX_HOPS {
+            break;
+        }
+        let next = client.redirect(url.clone()).await?;
+        if next == url {
+            break;
+        }
+        url = next;
+    }
+    Err(Error::InvalidInput(format!(
+        "Input '{input}' leads to {url}, which is not a video or live link."
+    )))
+}
+
+/// Resolve inputs concurrently, results are in the order of inputs.
+pub async fn resolve_all<S: AsRef<str>>(client: &Client, inputs: &[S]) -> Vec<Result<Target>> {
+    stream::iter(inputs)
+        .map(|input| resolve(client, input.as_ref()))
+        .buffered(CONCURRENCY)
+        .collect()
+        .await
+}
+
+#[test]
+fn parse_link_test() {
+    let video = |id: &str, page| {
+        Some(Target::Video {
+            id: id.parse().unwrap(),
+            page,
+        })
+    };
+    for (link, target) in [
+        (
+            "https://www.bilibili.com/video/BV17x411w7KC/?p=2&share=1",
+            video("av170001", Some(2)),
+        ),
+        (
+            "http://bilibili.com/video/av170001",
+            video("av170001", None),
+        ),
+        (
+            "https://m.bilibili.com/video/bv17x411w7KC?p=0",
+            video("av170001", None),
+        ),
+        (
+            "https://www.bilibili.com/festival/2022bnj?bvid=BV17x411w7KC&p=3",
+            video("av170001", Some(3)),
+        ),
+        (
+            "https://www.bilibili.com/list/946974?sid=1&bvid=BV17x411w7KC",
+            video("av170001", None),
+        ),
+        (
+            "https://www.bilibili.com/list/watchlater?oid=170001",
+            video("av170001", None),
+        ),
+        (
+            "https://www.bilibili.com/bangumi/play/ep508404?from=search",
+            Some(Target::Bangumi(BangumiId::Episode(508404))),
+        ),
+        (
+            "https://m.bilibili.com/bangumi/play/ss41410",
+            Some(Target::Bangumi(BangumiId::Season(41410))),
+        ),
+        ("https://www.bilibili.com/bangumi/play/md28339", None),
+        ("https://www.bilibili.com/festival/2022bnj", None),
+        ("https://space.bilibili.com/946974/video", None),
+        ("https://b23.tv/7ebAgB2", None),
+        (
+            "https://live.bilibili.com/21452505?spm_id_from=333.1007",
+            Some(Target::Live(21452505)),
+        ),
+        (
+            "https://live.bilibili.com/h5/21452505",
+            Some(Target::Live(21452505)),
+        ),
+        (
+            "https://live.bilibili.com/blanc/92613",
+            Some(Target::Live(92613)),
+        ),
+        ("https://live.bilibili.com/p/eden/area-tags", None),
+    ] {
+        assert_eq!(parse_link(&Url::parse(link).unwrap()), target, "{link}");
+    }
+    assert_eq!(
+        to_url("b23.tv/7ebAgB2").unwrap().as_str(),
+        "https://b23.tv/7ebAgB2"
+    );
+    assert!(to_url("ftp://b23.tv/7ebAgB2").is_none());
+    assert_eq!(
+        video("av170001", Some(2)).unwrap().to_string(),
+        "BV17x411w7KC?p=2"
+    );
+}
+
+#[tokio::test]
+async fn resolve_test() {
+    let client = Client::new();
+    let results = resolve_all(
+        &client,
+        &[
+            "av170001",
+            "www.bilibili.com/video/BV17x411w7KC?p=2",
+            "https://www.bilibili.com/bangumi/play/ss41410",
+            "EP508404",
+            "https://space.bilibili.com/946974",
+            "hello",
+        ],
+    )
+    .await;
+    let id = "av170001".parse().unwrap();
+    assert_eq!(
+        results[0].as_ref().unwrap(),
+        &Target::Video { id, page: None }
+    );
+    assert_eq!(
+        results[1].as_ref().unwrap(),
+        &Target::Video { id, page: Some(2) }
+    );
+    assert_eq!(
+        results[2].as_ref().unwrap(),
+        &Target::Bangumi(BangumiId::Season(41410))
+    );
+    assert_eq!(
+        results[3].as_ref().unwrap(),
+        &Target::Bangumi(BangumiId::Episode(508404))
+    );
+    assert!(matches!(results[4], Err(Error::InvalidInput(_))));
+    assert!(matches!(results[5], Err(Error::InvalidInput(_))));
+}
@@ -1,15 +1,23 @@
-use anyhow::Result;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use crate::item::TimeRange;
+use crate::{Error, Result};
 
 fn vec_to_string(v: Vec<u8>) -> String {
-    String::from_utf8(v).unwrap().trim().to_string()
+    String::from_utf8_lossy(&v).trim().to_string()
 }
 
-fn excute(args: &[&str]) -> Result<String> {
-    let out = Command::new(args[0]).args(&args[1..]).output()?;
+/// Run the command without blocking the runtime, so that downloads go on while ffmpeg runs.
+async fn excute(args: &[&str]) -> Result<String> {
+    let out = Command::new(args[0])
+        .args(&args[1..])
+        .output()
+        .await
+        .map_err(|e| Error::Transcode(format!("Failed to run {}: {e}", args[0])))?;
     match out.status.success() {
         true => Ok(vec_to_string(out.stdout)),
-        false => Err(anyhow::Error::msg(format!(
+        false => Err(Error::Transcode(format!(
             "Command not successful. This is stderr:\n{}",
             vec_to_string(out.stderr)
         ))),
@@ -22,30 +30,47 @@ static FFMPET: &str = "./ffmpeg.exe";
 #[cfg(target_os = "linux")]
 static FFMPET: &str = "./ffmpeg";
 
+/// Metadata are `(key, value)` pairs such as `("title", "...")`, later ones override earlier ones.
 pub async fn run(
     source: &str,
     output: &str,
     pic: Option<&str>,
     extension: &str,
-    title: &str,
-    artist: &str,
+    metadata: &[(&str, &str)],
+    range: Option<&TimeRange>,
 ) -> Result<()> {
-    let title = &format!("title={title}");
-    let artist = &format!("artist={artist}");
+    let metadata = metadata
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>();
+    let start = range.map(|range| range.start.to_string());
+    let end = range.and_then(|range| range.end).map(|end| end.to_string());
 
     let mut input_arg = vec![FFMPET, "-i", source];
 
-    if pic.is_some() {
-        input_arg.append(&mut vec!["-i", pic.unwrap()]);
+    if let Some(pic) = pic {
+        input_arg.append(&mut vec!["-i", pic]);
     }
 
-    input_arg.append(&mut vec!["-metadata", title]);
-    input_arg.append(&mut vec!["-metadata", artist]);
+    for metadata in &metadata {
+        input_arg.append(&mut vec!["-metadata", metadata]);
+    }
+
+    if let Some(start) = &start {
+        input_arg.append(&mut vec!["-ss", start]);
+    }
+    if let Some(end) = &end {
+        input_arg.append(&mut vec!["-to", end]);
+    }
 
     match extension {
         "flac" => input_arg.append(&mut vec!["-acodec", "flac"]),
         "m4a" => input_arg.append(&mut vec!["-c", "copy"]),
-        _ => panic!("Should not happened!"),
+        _ => {
+            return Err(Error::Transcode(format!(
+                "Unsupported extension '{extension}'."
+            )))
+        }
     }
 
     if pic.is_some() {
@@ -59,7 +84,69 @@ pub async fn run(
 
     input_arg.push(output);
 
-    excute(&input_arg)?;
+    excute(&input_arg).await?;
 
     Ok(())
 }
+
+/// Input list of the ffmpeg concat demuxer, `'` in paths is escaped.
+fn concat_list(parts: &[PathBuf]) -> String {
+    parts
+        .iter()
+        .map(|part| {
+            let path = std::path::absolute(part).unwrap_or_else(|_| part.clone());
+            format!(
+                "file '{}'\n",
+                path.display().to_string().replace('\'', "'\\''")
+            )
+        })
+        .collect()
+}
+
+/// Join recorded parts into one audio file without the video, in the order of parts.
+pub async fn remux(
+    parts: &[PathBuf],
+    output: &str,
+    extension: &str,
+    metadata: &[(&str, &str)],
+) -> Result<()> {
+    let codec: &[&str] = match extension {
+        "flac" => &["-acodec", "flac"],
+        "m4a" => &["-c:a", "copy"],
+        _ => {
+            return Err(Error::Transcode(format!(
+                "Unsupported extension '{extension}'."
+            )))
+        }
+    };
+    let list = Path::new(output).with_extension("parts.txt");
+    std::fs::write(&list, concat_list(parts))?;
+    let list_arg = list.to_string_lossy().to_string();
+    let metadata = metadata
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>();
+
+    let mut input_arg = vec![FFMPET, "-f", "concat", "-safe", "0", "-i", &list_arg, "-vn"];
+    for metadata in &metadata {
+        input_arg.append(&mut vec!["-metadata", metadata]);
+    }
+    input_arg.extend_from_slice(codec);
+    input_arg.push(output);
+
+    let result = excute(&input_arg).await;
+    let _ = std::fs::remove_file(&list);
+    result.map(|_| ())
+}
+
+#[test]
+fn concat_list_test() {
+    let list = concat_list(&[
+        PathBuf::from("/tmp/live.part1.flv"),
+        PathBuf::from("/tmp/it's live.part2.flv"),
+    ]);
+    assert_eq!(
+        list,
+        "file '/tmp/live.part1.flv'\nfile '/tmp/it'\\''s live.part2.flv'\n"
+    );
+}
@@ -0,0 +1,135 @@
+//! Login and refreshing cookies of an account.
+
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use vl::catcher::auth;
+use vl::cookies::{CookieFormat, CookieJar};
+use vl::{Client, ClientConfig, Context, Error};
+use wiremock::matchers::{body_string_contains, method, path, path_regex, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Mocks of the whole refresh flow for the csrf and refresh token of the account.
+async fn mock_refresh(server: &MockServer, csrf: &str, refresh_token: &str) {
+    Mock::given(method("GET"))
+        .and(path("/x/passport-login/web/cookie/info"))
+        .and(query_param("csrf", csrf))
+        .respond_with(json("cookie_info.json", server))
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/correspond/1/[0-9a-f]{256}$"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_raw(fixture("correspond.html", server), "text/html"),
+        )
+        .mount(server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/x/passport-login/web/cookie/refresh"))
+        .and(body_string_contains(format!("csrf={csrf}")))
+        .and(body_string_contains(
+            "refresh_csrf=b0cc8411ded2f9db2cff2edb3123acac",
+        ))
+        .and(body_string_contains(format!(
+            "refresh_token={refresh_token}"
+        )))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .append_header("Set-Cookie", "SESSDATA=new_session; Path=/")
+                .append_header("Set-Cookie", "bili_jct=new_csrf; Path=/")
+                .set_body_raw(fixture("cookie_refresh.json", server), "application/json"),
+        )
+        .expect(1)
+        .mount(server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/x/passport-login/web/confirm/refresh"))
+        .and(body_string_contains("csrf=new_csrf"))
+        .and(body_string_contains(format!(
+            "refresh_token={refresh_token}"
+        )))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_raw(r#"{"code":0,"message":"0","ttl":1}"#, "application/json"),
+        )
+        .expect(1)
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn refresh_cookies_test() {
+    let server = MockServer::start().await;
+    mock_refresh(&server, "old_csrf", "old_token").await;
+
+    let text = "127.0.0.1\tFALSE\t/\tFALSE\t0\tSESSDATA\told_session\n\
+        127.0.0.1\tFALSE\t/\tFALSE\t0\tbili_jct\told_csrf\n";
+    let jar = Arc::new(CookieJar::parse(text, CookieFormat::Netscape).unwrap());
+    let client = Client::with_config(&ClientConfig {
+        cookies: Some(Arc::clone(&jar)),
+        ..Default::default()
+    })
+    .unwrap();
+    let ctx = Context::new(client).with_base(&server.uri());
+
+    let refreshed = auth::refresh_cookies(&ctx, "old_token").await.unwrap();
+    assert_eq!(
+        refreshed,
+        Some(auth::Refreshed {
+            session: "new_session".to_string(),
+            csrf: "new_csrf".to_string(),
+            refresh_token: "c3fb1ff7f6e5d2a4b09a1c6e8b1d3c21".to_string(),
+        })
+    );
+    assert!(jar.changed());
+    assert_eq!(jar.get("bili_jct").as_deref(), Some("new_csrf"));
+
+    // Without login there is nothing to refresh.
+    let res = auth::refresh_cookies(&context(&server), "old_token").await;
+    assert!(matches!(res, Err(Error::NeedLogin(_))));
+}
+
+#[tokio::test]
+async fn login_refresh_test() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/x/passport-login/web/qrcode/poll"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .append_header("Set-Cookie", "SESSDATA=login_session; Path=/")
+                .append_header("Set-Cookie", "bili_jct=login_csrf; Path=/")
+                .set_body_raw(
+                    r#"{"code":0,"message":"0","data":{"url":"","refresh_token":"login_token","timestamp":1684466082000,"code":0,"message":""}}"#,
+                    "application/json",
+                ),
+        )
+        .mount(&server)
+        .await;
+    mock_refresh(&server, "login_csrf", "login_token").await;
+
+    let status = auth::qrcode_poll(&context(&server), "key").await.unwrap();
+    let auth::QrStatus::Confirmed {
+        session,
+        csrf,
+        refresh_token,
+    } = status
+    else {
+        panic!("login is not confirmed: {status:?}");
+    };
+    assert_eq!(
+        (session.as_str(), csrf.as_str()),
+        ("login_session", "login_csrf")
+    );
+
+    // A later run has only what login saved to the config file, without a cookies file.
+    // Set them for the mock server instead of bilibili.com like `set_session` and `set_csrf`.
+    let ctx = context(&server);
+    let cookies = ctx.client.cookies();
+    cookies.set("127.0.0.1", "SESSDATA", &session).unwrap();
+    cookies.set("127.0.0.1", "bili_jct", &csrf).unwrap();
+    let refreshed = auth::refresh_cookies(&ctx, &refresh_token).await.unwrap();
+    assert_eq!(refreshed.unwrap().csrf, "new_csrf");
+}
@@ -0,0 +1,320 @@
+//! Catchers of videos, bangumi, collections, live rooms and links.
+
+mod common;
+
+use common::*;
+use vl::catcher::collection::{self, Source};
+use vl::catcher::{link, live, pgc, view};
+use vl::downloader::{Downloader, Format};
+use vl::id::{BangumiId, VideoId};
+use vl::item::{ItemOptions, Pages};
+use vl::loader::StreamProtocol;
+use vl::resolve::{self, Target};
+use vl::{Client, ClientConfig, Error};
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+#[tokio::test]
+async fn view_test() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/x/web-interface/view"))
+        .and(query_param("bvid", "BV12g411r7mB"))
+        .respond_with(json("view.json", &server))
+        .mount(&server)
+        .await;
+
+    let res = view::api(&context(&server), "BV12g411r7mB").await.unwrap();
+    assert_eq!(
+        &res.title,
+        "【鹿乃×こはならむ】翻唱《ねぇねぇねぇ（呐呐呐。 ）》"
+    );
+    assert_eq!(res.pages[0].cid, 727383536);
+    assert_eq!(res.owner.name, "鹿乃まほろ");
+
+    // Aids are requested by the same bvid.
+    let aid = "BV12g411r7mB".parse::<VideoId>().unwrap().aid();
+    let res = view::api(&context(&server), &format!("av{aid}"))
+        .await
+        .unwrap();
+    assert_eq!(res.pages[0].cid, 727383536);
+}
+
+#[tokio::test]
+async fn playurl_test() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/x/player/playurl"))
+        .and(query_param("cid", "727383536"))
+        .and(query_param("fnval", "272"))
+        .and(header("cookie", "SESSDATA=session"))
+        .respond_with(json("playurl.json", &server))
+        .mount(&server)
+        .await;
+
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert("cookie", "SESSDATA=session".parse().unwrap());
+    let res = link::api(
+        &context(&server),
+        "BV12g411r7mB",
+        727383536,
+        16 | 256,
+        Some(headers),
+    )
+    .await
+    .unwrap();
+    assert_eq!(res.dash.audio.len(), 2);
+    assert_eq!(res.dash.audio[0].id, 30280);
+
+    let downloader = Downloader::builder().flac_allowed(true).build();
+    let (format, audio) = downloader.select_stream(&res.dash).unwrap();
+    assert_eq!(format, Format::Flac);
+    assert_eq!(audio.base_url, format!("{}/audio/30251.m4s", server.uri()));
+
+    let downloader = Downloader::builder().build();
+    let (format, audio) = downloader.select_stream(&res.dash).unwrap();
+    assert_eq!(format, Format::M4a);
+    assert_eq!(audio.id, 30280);
+
+    // Options of an item override the downloader's.
+    let options = |format: Option<Format>, quality: Option<&str>| ItemOptions {
+        format,
+        quality: quality.map(str::to_string),
+        ..Default::default()
+    };
+    let (format, audio) = downloader
+        .select_stream_with(&res.dash, &options(Some(Format::Flac), None))
+        .unwrap();
+    assert_eq!((format, audio.id), (Format::Flac, 30251));
+    let (format, audio) = downloader
+        .select_stream_with(&res.dash, &options(None, Some("64k")))
+        .unwrap();
+    assert_eq!((format, audio.id), (Format::M4a, 30216));
+    let (format, audio) = downloader
+        .select_stream_with(&res.dash, &options(Some(Format::Flac), Some("30216")))
+        .unwrap();
+    assert_eq!((format, audio.id), (Format::Flac, 30216));
+    let downloader = Downloader::builder().flac_allowed(true).build();
+    let (format, audio) = downloader
+        .select_stream_with(&res.dash, &options(Some(Format::M4a), None))
+        .unwrap();
+    assert_eq!((format, audio.id), (Format::M4a, 30280));
+    let (format, audio) = downloader
+        .select_stream_with(&res.dash, &options(None, Some("Hi-Res")))
+        .unwrap();
+    assert_eq!((format, audio.id), (Format::Flac, 30251));
+}
+
+#[tokio::test]
+async fn no_audio_test() {
+    let server = MockServer::start().await;
+    mock_json(&server, "/x/player/playurl", "playurl_no_audio.json").await;
+
+    let res = link::api(&context(&server), "BV12g411r7mB", 727383536, 16, None).await;
+    assert!(matches!(res, Err(Error::NoAudioStream)));
+
+    // A dash given by a library caller can have no audio too.
+    let dash: link::Dash =
+        serde_json::from_str(r#"{"audio":null,"dolby":null,"flac":null}"#).unwrap();
+    let downloader = Downloader::builder().flac_allowed(true).build();
+    assert!(matches!(
+        downloader.select_stream(&dash),
+        Err(Error::NoAudioStream)
+    ));
+    let options = ItemOptions {
+        quality: Some("192K".to_string()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        downloader.select_stream_with(&dash, &options),
+        Err(Error::NoAudioStream)
+    ));
+}
+
+#[tokio::test]
+async fn api_error_test() {
+    let server = MockServer::start().await;
+    mock_json(&server, "/x/web-interface/view", "not_found.json").await;
+    mock_json(&server, "/x/player/playurl", "risk_control.json").await;
+    let ctx = context(&server);
+
+    let res = view::api(&ctx, "BV1xx411c7mD").await;
+    assert!(matches!(res, Err(Error::NotFound(_))));
+
+    let res = link::api(&ctx, "BV1xx411c7mD", 1, 16, None).await;
+    assert!(matches!(res, Err(Error::RiskControl(_))));
+
+    let res = view::api(&ctx, "xx").await;
+    assert!(matches!(res, Err(Error::InvalidInput(_))));
+}
+
+#[tokio::test]
+async fn wbi_space_test() {
+    let server = MockServer::start().await;
+    mock_json(&server, "/x/web-interface/nav", "nav.json").await;
+    Mock::given(method("GET"))
+        .and(path("/x/space/wbi/arc/search"))
+        .and(query_param("mid", "316381099"))
+        .and(|request: &Request| {
+            let keys = request
+                .url
+                .query_pairs()
+                .map(|(k, _)| k)
+                .collect::<Vec<_>>();
+            keys.iter().any(|k| k == "wts") && keys.iter().any(|k| k == "w_rid")
+        })
+        .respond_with(json("space_arc.json", &server))
+        .mount(&server)
+        .await;
+    let ctx = context(&server);
+
+    let items = collection::api(&ctx, &Source::Uploader(316381099), None)
+        .await
+        .unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[1].bvid, "BV1VB4y1h7Pn");
+
+    // The mixin key is cached.
+    collection::api(&ctx, &Source::Uploader(316381099), None)
+        .await
+        .unwrap();
+    let navs = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|request| request.url.path() == "/x/web-interface/nav")
+        .count();
+    assert_eq!(navs, 1);
+}
+
+#[tokio::test]
+async fn pgc_test() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pgc/view/web/season"))
+        .and(query_param("ep_id", "700001"))
+        .respond_with(json("pgc_season.json", &server))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/pgc/player/web/playurl"))
+        .and(query_param("ep_id", "700001"))
+        .and(query_param("cid", "870001001"))
+        .respond_with(json("pgc_playurl.json", &server))
+        .mount(&server)
+        .await;
+    let ctx = context(&server);
+
+    // An episode gives the whole season, the ED is found in sections.
+    let id = BangumiId::Episode(700001);
+    let season = pgc::season(&ctx, id).await.unwrap();
+    assert_eq!(season.title, "孤独摇滚！");
+    assert_eq!(season.episodes.len(), 3);
+    assert!(season.section[1].episodes.is_empty());
+    let selected = season.select(id, None).unwrap();
+    assert_eq!(selected.len(), 1);
+    assert_eq!((selected[0].0, selected[0].1.name()), (1, "カラカラ"));
+
+    let id = BangumiId::Season(41410);
+    let tracks = |pages: Option<&str>| {
+        let pages = pages.map(|pages| pages.parse::<Pages>().unwrap());
+        season
+            .select(id, pages.as_ref())
+            .map(|selected| selected.iter().map(|(track, _)| *track).collect::<Vec<_>>())
+    };
+    assert_eq!(tracks(None).unwrap(), vec![1, 2, 3]);
+    assert_eq!(tracks(Some("3,1")).unwrap(), vec![3, 1]);
+    assert!(matches!(tracks(Some("4-")), Err(Error::NotFound(_))));
+    assert_eq!(season.episodes[2].name(), "3");
+
+    let res = pgc::playurl(&ctx, 700001, 870001001, 16 | 256)
+        .await
+        .unwrap();
+    assert_eq!(res.dash.audio[0].id, 30280);
+}
+
+#[tokio::test]
+async fn live_test() {
+    let server = MockServer::start().await;
+    mock_live_room(&server).await;
+    let ctx = context(&server);
+
+    let room = live::room_info(&ctx, 21452505).await.unwrap();
+    assert!(room.is_live());
+    assert_eq!(room.room_info.title, "【歌回】深夜点歌台");
+    assert_eq!(room.anchor_info.base_info.uname, "七海Nana7mi");
+
+    let flv = live::stream(&ctx, 21452505, StreamProtocol::Flv)
+        .await
+        .unwrap();
+    assert_eq!(
+        flv.url,
+        format!(
+            "{}/live-bvc/flv/live_21452505.flv?expires=1709220600&len=0",
+            server.uri()
+        )
+    );
+    assert_eq!(flv.extension, "flv");
+    // Ts has no url here, so avc fmp4 is picked over hevc.
+    let hls = live::stream(&ctx, 21452505, StreamProtocol::Hls)
+        .await
+        .unwrap();
+    assert_eq!(
+        hls.url,
+        format!(
+            "{}/live-bvc/hls/master.m3u8?expires=1709220600",
+            server.uri()
+        )
+    );
+    assert_eq!(hls.extension, "m4s");
+}
+
+#[tokio::test]
+async fn resolve_redirect_test() {
+    let server = MockServer::start().await;
+    let redirect =
+        |location: String| ResponseTemplate::new(302).insert_header("Location", location.as_str());
+    Mock::given(method("HEAD"))
+        .and(path("/7ebAgB2"))
+        .respond_with(redirect(format!("{}/hop", server.uri())))
+        .mount(&server)
+        .await;
+    Mock::given(method("HEAD"))
+        .and(path("/hop"))
+        .respond_with(redirect(
+            "https://m.bilibili.com/video/av170001?p=3&share_source=copy".to_string(),
+        ))
+        .mount(&server)
+        .await;
+    Mock::given(method("HEAD"))
+        .and(path("/space"))
+        .respond_with(redirect("https://space.bilibili.com/946974".to_string()))
+        .mount(&server)
+        .await;
+    // Every hop is checked, the final page is never visited.
+    let client = Client::with_config(&ClientConfig {
+        max_redirects: 0,
+        ..Default::default()
+    })
+    .unwrap();
+    let hosts = ["127.0.0.1"];
+
+    let link = format!("{}/7ebAgB2", server.uri());
+    let target = resolve::resolve_with(&client, &link, &hosts).await.unwrap();
+    assert_eq!(
+        target,
+        Target::Video {
+            id: "BV17x411w7KC".parse().unwrap(),
+            page: Some(3)
+        }
+    );
+
+    let link = format!("{}/space", server.uri());
+    let res = resolve::resolve_with(&client, &link, &hosts).await;
+    assert!(matches!(res, Err(Error::InvalidInput(_))));
+    // Not a short link host, so there is no request.
+    let res = resolve::resolve(&client, &format!("{}/7ebAgB2", server.uri())).await;
+    assert!(matches!(res, Err(Error::InvalidInput(_))));
+    assert_eq!(server.received_requests().await.unwrap().len(), 3);
+}
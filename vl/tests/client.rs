@@ -0,0 +1,86 @@
+//! Timeouts, CA certificates and cookies of the client.
+
+mod common;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::*;
+use vl::catcher::view;
+use vl::cookies::{CookieFormat, CookieJar};
+use vl::{Client, ClientConfig, Context, Error};
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn read_timeout_test() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/x/web-interface/view"))
+        .respond_with(json("view.json", &server).set_delay(Duration::from_secs(2)))
+        .mount(&server)
+        .await;
+    let client = Client::with_config(&ClientConfig {
+        read_timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
+    })
+    .unwrap();
+    let ctx = Context::new(client).with_base(&server.uri());
+
+    let res = view::api(&ctx, "BV12g411r7mB").await;
+    assert!(matches!(res, Err(Error::Timeout(_))));
+}
+
+#[test]
+fn ca_file_test() {
+    let mut config = ClientConfig {
+        ca_files: vec![
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ca_bundle.pem")
+        ],
+        ca_only: true,
+        ..Default::default()
+    };
+    assert_eq!(config.certificates().unwrap().len(), 2);
+    assert!(Client::with_config(&config).is_ok());
+
+    config.ca_files =
+        vec![PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/view.json")];
+    assert!(matches!(config.certificates(), Err(Error::InvalidInput(_))));
+}
+
+#[tokio::test]
+async fn cookies_rotate_test() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/x/web-interface/view"))
+        .and(header("cookie", "bili_jct=old"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Set-Cookie", "bili_jct=new; Path=/; Max-Age=3600")
+                .set_body_raw(fixture("view.json", &server), "application/json"),
+        )
+        .mount(&server)
+        .await;
+    let text = "# Netscape HTTP Cookie File\n127.0.0.1\tFALSE\t/\tFALSE\t0\tbili_jct\told\n";
+    let jar = Arc::new(CookieJar::parse(text, CookieFormat::Netscape).unwrap());
+    let client = Client::with_config(&ClientConfig {
+        cookies: Some(Arc::clone(&jar)),
+        ..Default::default()
+    })
+    .unwrap();
+    let ctx = Context::new(client).with_base(&server.uri());
+
+    view::api(&ctx, "BV12g411r7mB").await.unwrap();
+    assert!(jar.changed());
+    assert_eq!(jar.get("bili_jct").as_deref(), Some("new"));
+
+    let dir = temp_dir("cookies");
+    let file = dir.join("cookies.txt");
+    jar.save(&file, CookieFormat::Netscape).unwrap();
+    assert!(!jar.changed());
+    let (saved, format) = CookieJar::load(&file).unwrap();
+    assert_eq!(format, CookieFormat::Netscape);
+    assert_eq!(saved.get("bili_jct").as_deref(), Some("new"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
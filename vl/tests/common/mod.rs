@@ -0,0 +1,122 @@
+//! Helpers of the tests against a local mock server with recorded responses.
+// Every test binary uses a part of them.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use vl::downloader::Downloader;
+use vl::event::Event;
+use vl::Context;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+pub fn fixture(name: &str, server: &MockServer) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    std::fs::read_to_string(path)
+        .unwrap()
+        .replace("{base}", &server.uri())
+}
+
+/// A recorded api response.
+pub fn json(name: &str, server: &MockServer) -> ResponseTemplate {
+    ResponseTemplate::new(200)
+        .set_body_raw(fixture(name, server), "application/json; charset=utf-8")
+}
+
+pub async fn mock_json(server: &MockServer, api: &str, name: &str) {
+    Mock::given(method("GET"))
+        .and(path(api))
+        .respond_with(json(name, server))
+        .mount(server)
+        .await;
+}
+
+/// Room info and play info of a live room on air.
+pub async fn mock_live_room(server: &MockServer) {
+    mock_json(
+        server,
+        "/xlive/web-room/v1/index/getInfoByRoom",
+        "live_room_info.json",
+    )
+    .await;
+    mock_json(
+        server,
+        "/xlive/web-room/v2/index/getRoomPlayInfo",
+        "live_play_info.json",
+    )
+    .await;
+}
+
+pub fn context(server: &MockServer) -> Context {
+    Context::default().with_base(&server.uri())
+}
+
+/// Downloader against the server saving to `dir`, with the events it sends.
+pub fn event_downloader(server: &MockServer, dir: &Path) -> (Downloader, Arc<Mutex<Vec<Event>>>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let received = Arc::clone(&events);
+    let downloader = Downloader::builder()
+        .context(context(server))
+        .output_dir(dir)
+        .on_event(move |event| received.lock().unwrap().push(event))
+        .build();
+    (downloader, events)
+}
+
+/// Serves the body in ranges like a CDN does.
+pub struct RangeResponder(pub Vec<u8>);
+
+impl Respond for RangeResponder {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let range = request
+            .headers
+            .get(&"range".into())
+            .map(|v| v.as_str().to_string());
+        let range = match range {
+            None => return ResponseTemplate::new(200).set_body_bytes(self.0.clone()),
+            Some(range) => range,
+        };
+        let (start, end) = range.trim_start_matches("bytes=").split_once('-').unwrap();
+        let start = start.parse::<usize>().unwrap();
+        let end = match end {
+            "" => self.0.len() - 1,
+            end => end.parse::<usize>().unwrap(),
+        };
+        ResponseTemplate::new(206)
+            .insert_header(
+                "Content-Range",
+                format!("bytes {start}-{end}/{}", self.0.len()).as_str(),
+            )
+            .set_body_bytes(self.0[start..=end].to_vec())
+    }
+}
+
+pub fn audio_bytes() -> Vec<u8> {
+    (0..1 << 20).map(|i: u32| (i * 31 % 251) as u8).collect()
+}
+
+pub async fn mock_audio(server: &MockServer, api: &str, body: Vec<u8>, ranges: bool) {
+    let mut head = ResponseTemplate::new(200).set_body_bytes(body.clone());
+    if ranges {
+        head = head.insert_header("Accept-Ranges", "bytes");
+    }
+    Mock::given(method("HEAD"))
+        .and(path(api))
+        .respond_with(head)
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(api))
+        .respond_with(RangeResponder(body))
+        .mount(server)
+        .await;
+}
+
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vl-offline-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,102 @@
+//! Downloading items end to end.
+
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use vl::catcher::pgc;
+use vl::event::Event;
+use vl::id::BangumiId;
+use vl::item::{Item, ItemOptions};
+use vl::loader::StreamProtocol;
+use vl::Error;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn bangumi_download_test() {
+    let server = MockServer::start().await;
+    mock_json(&server, "/pgc/view/web/season", "pgc_season.json").await;
+    mock_json(&server, "/pgc/player/web/playurl", "pgc_playurl.json").await;
+    mock_audio(&server, "/audio/30280.m4s", audio_bytes(), true).await;
+    let dir = temp_dir("bangumi");
+    let (downloader, events) = event_downloader(&server, &dir);
+
+    let season = pgc::season(&context(&server), BangumiId::Season(41410))
+        .await
+        .unwrap();
+    assert_eq!(
+        downloader.episode_filename(&season, &season.episodes[0], &ItemOptions::default(), 1, 2),
+        "孤独摇滚！ 1 转动的星球"
+    );
+
+    let options = ItemOptions {
+        pages: Some("1-2".parse().unwrap()),
+        ..Default::default()
+    };
+    let results = downloader.run_items(&[Item::new("ss41410", options)]).await;
+    // Each selected episode is downloaded and transformed on its own.
+    assert_eq!(results.len(), 2);
+    let events = events.lock().unwrap().clone();
+    assert!(events.contains(&Event::MetadataFetched {
+        index: 1,
+        bvid: "ss41410".to_string(),
+        title: "孤独摇滚！".to_string(),
+        owner: "哔哩哔哩番剧".to_string(),
+        duration: 2840,
+        pages: 2,
+    }));
+    let streams = events
+        .iter()
+        .filter(|event| matches!(event, Event::StreamSelected { quality: 30280, .. }))
+        .count();
+    assert_eq!(streams, 2);
+    let playurls = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|request| request.url.path() == "/pgc/player/web/playurl")
+        .count();
+    assert_eq!(playurls, 2);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn record_room_test() {
+    let server = MockServer::start().await;
+    mock_live_room(&server).await;
+    Mock::given(method("GET"))
+        .and(path("/live-bvc/flv/live_21452505.flv"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(audio_bytes()[..4096].to_vec()))
+        .mount(&server)
+        .await;
+    let dir = temp_dir("record-room");
+    let (downloader, events) = event_downloader(&server, &dir);
+
+    let options = ItemOptions {
+        name: Some("karaoke".to_string()),
+        ..Default::default()
+    };
+    let result = downloader
+        .record(
+            21452505,
+            Some(Duration::from_millis(200)),
+            StreamProtocol::Flv,
+            &options,
+        )
+        .await;
+    // Remuxing fails without ffmpeg, the parts are removed anyway.
+    assert!(matches!(result, Err(Error::Transcode(_))));
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    assert!(events.lock().unwrap().contains(&Event::MetadataFetched {
+        index: 1,
+        bvid: "21452505".to_string(),
+        title: "【歌回】深夜点歌台".to_string(),
+        owner: "七海Nana7mi".to_string(),
+        duration: 0,
+        pages: 1,
+    }));
+    std::fs::remove_dir_all(dir).unwrap();
+}
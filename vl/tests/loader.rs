@@ -0,0 +1,246 @@
+//! Downloading and recording streams.
+
+mod common;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::*;
+use vl::loader::{judge, load, load_with_progress, LiveStream, Recorder, StreamProtocol};
+use vl::{Client, Error};
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+#[tokio::test]
+async fn range_download_test() {
+    let server = MockServer::start().await;
+    let body = audio_bytes();
+    mock_audio(&server, "/audio/30280.m4s", body.clone(), true).await;
+    let ctx = context(&server);
+    let url = format!("{}/audio/30280.m4s", server.uri());
+
+    let (length, ranges) = judge(&ctx.client, &url).await.unwrap();
+    assert_eq!(length, body.len() as u64);
+    assert!(ranges);
+
+    let dir = temp_dir("range");
+    let downloaded = Arc::new(AtomicU64::new(0));
+    let reported = Arc::clone(&downloaded);
+    load_with_progress(
+        &ctx.client,
+        &url,
+        "audio",
+        &dir.to_string_lossy(),
+        "m4s",
+        Some(Arc::new(move |downloaded, _| {
+            reported.fetch_max(downloaded, Ordering::SeqCst);
+        })),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(std::fs::read(dir.join("audio.m4s")).unwrap(), body);
+    assert_eq!(downloaded.load(Ordering::SeqCst), body.len() as u64);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn tiny_range_download_test() {
+    let server = MockServer::start().await;
+    mock_audio(&server, "/empty.m4s", vec![], true).await;
+    mock_audio(&server, "/tiny.m4s", vec![1, 2, 3], true).await;
+    let ctx = context(&server);
+    let dir = temp_dir("tiny-range");
+
+    // Bodies smaller than the number of blocks are fetched in one stream.
+    for (name, body) in [("empty", vec![]), ("tiny", vec![1, 2, 3])] {
+        let url = format!("{}/{name}.m4s", server.uri());
+        load(&ctx.client, &url, name, &dir.to_string_lossy(), "m4s")
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read(dir.join(name).with_extension("m4s")).unwrap(),
+            body
+        );
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn single_download_test() {
+    let server = MockServer::start().await;
+    let body = audio_bytes();
+    mock_audio(&server, "/cover.jpg", body.clone(), false).await;
+    let ctx = context(&server);
+
+    let dir = temp_dir("single");
+    let url = format!("{}/cover.jpg", server.uri());
+    load(&ctx.client, &url, "cover", &dir.to_string_lossy(), "jpg")
+        .await
+        .unwrap();
+
+    assert_eq!(std::fs::read(dir.join("cover.jpg")).unwrap(), body);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn download_error_test() {
+    let server = MockServer::start().await;
+    let body = audio_bytes();
+    // Accept-Ranges is announced but ranges are ignored.
+    Mock::given(method("HEAD"))
+        .and(path("/audio/30216.m4s"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Accept-Ranges", "bytes")
+                .set_body_bytes(body.clone()),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/audio/30216.m4s"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(body))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/expired.m4s"))
+        .respond_with(ResponseTemplate::new(403))
+        .mount(&server)
+        .await;
+    let ctx = context(&server);
+    let dir = temp_dir("error");
+    let dir_str = dir.to_string_lossy();
+
+    let url = format!("{}/audio/30216.m4s", server.uri());
+    let res = load(&ctx.client, &url, "audio", &dir_str, "m4s").await;
+    assert!(matches!(res, Err(Error::RangeUnsupported)));
+
+    let url = format!("{}/expired.m4s", server.uri());
+    let res = load(&ctx.client, &url, "expired", &dir_str, "m4s").await;
+    assert!(matches!(res, Err(Error::Network(_))));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn record_flv_test() {
+    let server = MockServer::start().await;
+    let body = audio_bytes()[..4096].to_vec();
+    Mock::given(method("GET"))
+        .and(path("/live.flv"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(body.clone()))
+        .mount(&server)
+        .await;
+    let dir = temp_dir("record-flv");
+    let stream = LiveStream {
+        url: format!("{}/live.flv", server.uri()),
+        protocol: StreamProtocol::Flv,
+        extension: "flv".to_string(),
+    };
+
+    // The response ends twice before the live ends, each connection is a part.
+    let connections = AtomicU64::new(0);
+    let recording = Recorder::new(Client::new())
+        .retry_delay(Duration::ZERO)
+        .record(
+            || async {
+                match connections.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => Ok(stream.clone()),
+                    _ => Err(Error::NotFound("Room 1 is not live.".to_string())),
+                }
+            },
+            &dir.join("live"),
+        )
+        .await
+        .unwrap();
+    assert!(recording.ended);
+    assert_eq!(recording.bytes, 2 * body.len() as u64);
+    assert_eq!(
+        recording.parts,
+        vec![dir.join("live.part1.flv"), dir.join("live.part2.flv")]
+    );
+    assert_eq!(std::fs::read(&recording.parts[1]).unwrap(), body);
+
+    // Failures without data are retried until they run out.
+    let result = Recorder::new(Client::new())
+        .retry_delay(Duration::ZERO)
+        .max_retries(2)
+        .record(|| async { Err(Error::NoAudioStream) }, &dir.join("failed"))
+        .await;
+    assert!(matches!(result, Err(Error::NoAudioStream)));
+
+    // Reconnecting stops at the duration and keeps what is recorded.
+    let recording = Recorder::new(Client::new())
+        .duration(Some(Duration::from_millis(300)))
+        .retry_delay(Duration::from_millis(100))
+        .record(|| async { Ok(stream.clone()) }, &dir.join("timed"))
+        .await
+        .unwrap();
+    assert!(!recording.ended);
+    assert!(!recording.parts.is_empty());
+    assert!(recording.elapsed < Duration::from_secs(3));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// Serves a sliding live playlist, the segments move on by one every two requests.
+struct PlaylistResponder(AtomicU64);
+
+impl Respond for PlaylistResponder {
+    fn respond(&self, _: &Request) -> ResponseTemplate {
+        let sequence = self.0.fetch_add(1, Ordering::SeqCst) / 2 + 1;
+        let mut playlist = format!(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:0\n#EXT-X-MEDIA-SEQUENCE:{sequence}\n\
+             #EXT-X-MAP:URI=\"init.m4s\"\n#EXTINF:1.0,\n{sequence}.m4s\n#EXTINF:1.0,\n{}.m4s\n",
+            sequence + 1
+        );
+        if sequence == 2 {
+            playlist.push_str("#EXT-X-ENDLIST\n");
+        }
+        ResponseTemplate::new(200).set_body_string(playlist)
+    }
+}
+
+#[tokio::test]
+async fn record_hls_test() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/hls/master.m3u8"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(
+            "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1280000\nvariant/index.m3u8?token=1\n",
+        ))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/hls/variant/index.m3u8"))
+        .and(query_param("token", "1"))
+        .respond_with(PlaylistResponder(AtomicU64::new(0)))
+        .mount(&server)
+        .await;
+    for segment in ["init", "1", "2", "3"] {
+        Mock::given(method("GET"))
+            .and(path(format!("/hls/variant/{segment}.m4s")))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!("<{segment}>")))
+            .mount(&server)
+            .await;
+    }
+    let dir = temp_dir("record-hls");
+    let stream = LiveStream {
+        url: format!("{}/hls/master.m3u8", server.uri()),
+        protocol: StreamProtocol::Hls,
+        extension: "m4s".to_string(),
+    };
+
+    let recording = Recorder::new(Client::new())
+        .record(|| async { Ok(stream.clone()) }, &dir.join("live"))
+        .await
+        .unwrap();
+    // The init segment is written once and segments in more than one playlist are not repeated.
+    assert!(recording.ended);
+    assert_eq!(recording.parts, vec![dir.join("live.part1.m4s")]);
+    assert_eq!(
+        std::fs::read_to_string(&recording.parts[0]).unwrap(),
+        "<init><1><2><3>"
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}